@@ -1,3 +1,4 @@
 #!/bin/bash
 
-./github-pr-mirror-bot > >(tee -a mirror_bot_stdout_$(date +%Y-%m-%d).log) 2> >(tee -a mirror_bot_stderr_$(date +%Y-%m-%d).log >&2)
\ No newline at end of file
+# Logging to files is handled by the bot itself, see 'logging' in simple_mirror_config.yml.
+./github-pr-mirror-bot
@@ -0,0 +1,155 @@
+use crate::{AppConfig, AssigneeMode};
+use octocrab::{models::repos::RepoCommit, Octocrab};
+use tracing::{error, warn};
+
+/// Where GitHub looks for a CODEOWNERS file, in the order it looks.
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Picks who to assign to a PR or issue about the given upstream merge commit, according to `pr_settings`.
+pub async fn pick_assignees(octocrab: &Octocrab, config: &AppConfig, merge_commit: Option<&RepoCommit>) -> Vec<String> {
+    let settings = &config.pr_settings;
+
+    return match settings.assignee_mode {
+        AssigneeMode::Static => settings.assignees.clone(),
+        AssigneeMode::RoundRobin => {
+            if settings.assignees.is_empty() {
+                return Vec::new();
+            }
+
+            let turn = config.state.assignee_rotation.get();
+            vec![settings.assignees[turn % settings.assignees.len()].clone()]
+        }
+        AssigneeMode::Codeowners => {
+            let changed_files: Vec<String> = merge_commit
+                .and_then(|c| c.files.as_ref())
+                .map(|files| files.iter().map(|f| f.filename.clone()).collect())
+                .unwrap_or_default();
+
+            let owners = match get_codeowners(octocrab, config).await {
+                Some(codeowners) => owners_of(&codeowners, &changed_files),
+                None => Vec::new(),
+            };
+
+            // Fall back on the static list if nobody owns the changes.
+            match owners.is_empty() {
+                true => settings.assignees.clone(),
+                false => owners,
+            }
+        }
+    };
+}
+
+/// Moves the round robin on once a PR or issue was made with the pick from `pick_assignees`, so failed attempts don't use up turns.
+/// The state file is saved straight away, or the turn would be forgotten if the run doesn't finish.
+pub fn pass_turn(config: &AppConfig) {
+    let settings = &config.pr_settings;
+    if settings.assignee_mode != AssigneeMode::RoundRobin || settings.assignees.is_empty() {
+        return;
+    }
+
+    let turn = config.state.assignee_rotation.get();
+    config.state.assignee_rotation.set(turn.wrapping_add(1));
+
+    if let Err(e) = config.state.save(config) {
+        error!("Failed to save the assignee rotation: {}", e);
+    }
+}
+
+/// Gets the CODEOWNERS file of `into_repo`, if it has one.
+async fn get_codeowners(octocrab: &Octocrab, config: &AppConfig) -> Option<String> {
+    if !config.target_on_github() {
+        return None;
+    }
+
+    for path in CODEOWNERS_PATHS {
+        let content = octocrab
+            .repos(&config.into_repo.owner, &config.into_repo.name)
+            .get_content()
+            .path(path)
+            .r#ref(&config.into_repo.branch)
+            .send()
+            .await;
+
+        if let Some(file) = content.ok().and_then(|mut c| c.take_items().into_iter().next()) {
+            return file.decoded_content();
+        }
+    }
+
+    warn!("No CODEOWNERS file found in {}/{}.", config.into_repo.owner, config.into_repo.name);
+    return None;
+}
+
+/// Finds the users owning any of the given paths. Teams and email owners can't be assigned, so they're left out.
+fn owners_of(codeowners: &str, paths: &[String]) -> Vec<String> {
+    let rules: Vec<(&str, Vec<&str>)> = codeowners
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty())
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace();
+            let pattern = parts.next()?;
+            return Some((pattern, parts.collect()));
+        })
+        .collect();
+
+    let mut owners = Vec::new();
+
+    for path in paths {
+        // The last matching rule takes precedence.
+        let rule = rules.iter().rev().find(|(pattern, _)| pattern_matches(pattern, path));
+
+        for owner in rule.map(|(_, o)| o.as_slice()).unwrap_or_default() {
+            let user = match owner.strip_prefix('@') {
+                Some(u) if !u.contains('/') => u.to_string(),
+                _ => continue,
+            };
+
+            if !owners.contains(&user) {
+                owners.push(user);
+            }
+        }
+    }
+
+    return owners;
+}
+
+/// Matches a path against a CODEOWNERS (gitignore-style) pattern.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let directory = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+
+    // Patterns without a slash in them match at any depth.
+    let anchored = pattern.starts_with('/') || pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+
+    let pattern_parts: Vec<&str> = pattern.split('/').collect();
+    let path_parts: Vec<&str> = path.split('/').collect();
+
+    if anchored {
+        return parts_match(&pattern_parts, &path_parts, directory);
+    }
+
+    return (0..path_parts.len()).any(|start| parts_match(&pattern_parts, &path_parts[start..], directory));
+}
+
+/// Matches path segments against pattern segments, where a pattern also matches everything inside the directories it matches.
+fn parts_match(pattern: &[&str], path: &[&str], directory: bool) -> bool {
+    return match (pattern.first(), path.first()) {
+        (None, None) => !directory,
+        (None, Some(_)) => true,
+        (Some(&"**"), _) => (0..=path.len()).any(|skip| parts_match(&pattern[1..], &path[skip..], directory)),
+        (Some(_), None) => false,
+        (Some(p), Some(s)) => segment_matches(p.as_bytes(), s.as_bytes()) && parts_match(&pattern[1..], &path[1..], directory),
+    };
+}
+
+/// Matches a single path segment against a glob with `*` and `?`.
+fn segment_matches(pattern: &[u8], segment: &[u8]) -> bool {
+    return match (pattern.first(), segment.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => segment_matches(&pattern[1..], segment) || (!segment.is_empty() && segment_matches(pattern, &segment[1..])),
+        (Some(b'?'), Some(_)) => segment_matches(&pattern[1..], &segment[1..]),
+        (Some(p), Some(s)) => p == s && segment_matches(&pattern[1..], &segment[1..]),
+        _ => false,
+    };
+}
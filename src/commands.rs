@@ -0,0 +1,283 @@
+use crate::{git_utils, mirrors::{self, MirrorPr}, pr_template::PrTemplate, report::PhaseTimings, AppConfig, Error, NO_NET_ACTIVITY};
+use chrono::Utc;
+use git2::Repository;
+use octocrab::{models::{issues::Comment, Author, IssueState}, params, Octocrab};
+use tracing::{error, info, warn};
+
+/// A command maintainers can give the bot by commenting on its mirror PRs or failure issues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    /// Recreates the cherry-pick on the latest target branch and force-pushes it to the mirror PR.
+    Rebase,
+    /// Marks the upstream PR as intentionally not mirrored, and closes the mirror PR or failure issue.
+    Skip,
+    /// Attempts a failed mirror again.
+    Retry,
+    /// Flips a draft mirror PR to ready for review.
+    Ready,
+}
+
+impl Command {
+    fn parse(line: &str) -> Option<Command> {
+        return match line.split_whitespace().next()? {
+            "/rebase" => Some(Command::Rebase),
+            "/skip" => Some(Command::Skip),
+            "/retry" => Some(Command::Retry),
+            "/ready" => Some(Command::Ready),
+            _ => None,
+        };
+    }
+}
+
+/// What a command was left on.
+enum Target {
+    Mirror(Box<MirrorPr>),
+    /// An issue made by `make_issue`, and the upstream PR it's about.
+    FailureIssue(u64, u64),
+}
+
+#[derive(serde::Deserialize)]
+struct Permission {
+    permission: String,
+}
+
+/// Checks for new commands on the bot's mirror PRs and failure issues since the last check, and carries them out.
+pub async fn handle_commands(octocrab: &Octocrab, config: &mut AppConfig, bot_info: &Author) {
+    let checked_at = Utc::now();
+
+    let since = match config.state.commands_checked_at {
+        Some(s) => s,
+        None => {
+            // Don't dig up commands left before the bot was listening.
+            info!("Checking for commands from now on.");
+            config.state.commands_checked_at = Some(checked_at);
+            return;
+        }
+    };
+
+    let comments = match get_comments_since(octocrab, config, since).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to get comments to check for commands: {}", e);
+            return;
+        }
+    };
+
+    let logins = mirrors::bot_logins(octocrab, bot_info).await;
+    let mut repo: Option<Repository> = None;
+
+    for comment in comments.iter().filter(|c| c.created_at > since && !logins.contains(&c.user.login)) {
+        let command = match comment.body.as_deref().unwrap_or_default().lines().find_map(Command::parse) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let number = match comment.issue_url.as_ref().and_then(|u| u.path_segments()?.next_back()?.parse().ok()) {
+            Some(n) => n,
+            None => continue,
+        };
+
+        let target = match get_target(octocrab, config, bot_info, &logins, number).await {
+            Some(t) => t,
+            None => continue,
+        };
+
+        info!("{} asked for {:?} on #{}.", comment.user.login, command, number);
+
+        if !has_write_permission(octocrab, config, &comment.user.login).await {
+            reply(octocrab, config, number, format!("Sorry @{}, only users with write access can use bot commands.", comment.user.login)).await;
+            continue;
+        }
+
+        let result = match (command, &target) {
+            (Command::Rebase, Target::Mirror(mirror)) => match with_repo(&mut repo, config, bot_info) {
+                Ok(r) => rebase(r, config, bot_info, mirror),
+                Err(e) => Err(e),
+            },
+            (Command::Retry, Target::FailureIssue(_, upstream_number)) => match with_repo(&mut repo, config, bot_info) {
+                Ok(r) => retry(r, octocrab, config, bot_info, number, *upstream_number).await,
+                Err(e) => Err(e),
+            },
+            (Command::Skip, _) => skip(octocrab, config, &target).await,
+            (Command::Ready, Target::Mirror(mirror)) => ready(octocrab, mirror).await,
+            (Command::Rebase | Command::Ready, Target::FailureIssue(..)) => Err(Error::from("That command only works on mirror PRs.")),
+            (Command::Retry, Target::Mirror(_)) => Err(Error::from("That command only works on failure issues, use /rebase on mirror PRs.")),
+        };
+
+        let message = match result {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Command {:?} on #{} failed: {}", command, number, e);
+                format!("Failed to carry out that command: {}", e)
+            }
+        };
+
+        reply(octocrab, config, number, message).await;
+
+        if let Some(r) = &repo {
+            if git_utils::reset_repo(r, config).is_err() {
+                error!("Failed to reset repository after command on #{}.", number);
+                repo = None;
+            }
+        }
+    }
+
+    config.state.commands_checked_at = Some(checked_at);
+}
+
+async fn get_comments_since(octocrab: &Octocrab, config: &AppConfig, since: chrono::DateTime<Utc>) -> Result<Vec<Comment>, Error> {
+    let first_page = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .list_issue_comments()
+        .since(since)
+        .sort(params::issues::Sort::Created)
+        .direction(params::Direction::Ascending)
+        .per_page(100)
+        .send()
+        .await?;
+
+    return Ok(octocrab.all_pages(first_page).await?);
+}
+
+/// Works out whether an issue or PR is one the bot made.
+async fn get_target(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, logins: &[String], number: u64) -> Option<Target> {
+    let issue = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .get(number)
+        .await
+        .inspect_err(|e| error!("Failed to get #{} for a command: {}", number, e))
+        .ok()?;
+
+    if issue.pull_request.is_none() {
+        // Anyone can open an issue with a failure issue's title.
+        if !logins.contains(&issue.user.login) {
+            return None;
+        }
+        return PrTemplate::number_from_failure_title(&issue.title).map(|n| Target::FailureIssue(number, n));
+    }
+
+    let pr = octocrab
+        .pulls(&config.into_repo.owner, &config.into_repo.name)
+        .get(number)
+        .await
+        .inspect_err(|e| error!("Failed to get PR #{} for a command: {}", number, e))
+        .ok()?;
+
+    return mirrors::to_mirror(pr, config, bot_info, logins).map(|m| Target::Mirror(Box::new(m)));
+}
+
+async fn has_write_permission(octocrab: &Octocrab, config: &AppConfig, login: &str) -> bool {
+    let route = format!("/repos/{}/{}/collaborators/{}/permission", config.into_repo.owner, config.into_repo.name, login);
+
+    return match octocrab.get::<Permission, _, ()>(route, None).await {
+        Ok(p) => p.permission == "admin" || p.permission == "write",
+        Err(e) => {
+            error!("Failed to get permissions of {}: {}", login, e);
+            false
+        }
+    };
+}
+
+/// Opens the local repo the first time a command needs it.
+fn with_repo<'a>(repo: &'a mut Option<Repository>, config: &AppConfig, bot_info: &Author) -> Result<&'a Repository, Error> {
+    if repo.is_none() {
+        *repo = Some(git_utils::ensure_repo(config, bot_info)?);
+    }
+
+    return Ok(repo.as_ref().unwrap());
+}
+
+fn rebase(repo: &Repository, config: &AppConfig, bot_info: &Author, mirror: &MirrorPr) -> Result<String, Error> {
+    if mirror.upstream_sha.is_empty() {
+        return Err(Error::from("Couldn't find the upstream merge commit in this PR's body."));
+    }
+
+    crate::create_mirror_commit(repo, config, bot_info, &mirror.pr.head.ref_field, &mirror.upstream_sha, None, &mut PhaseTimings::default())?;
+    git_utils::push_to_remote(repo, config, bot_info)?;
+
+    return Ok(format!("Recreated this mirror on top of the latest {}.", config.into_repo.branch));
+}
+
+async fn retry(repo: &Repository, octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, issue_number: u64, upstream_number: u64) -> Result<String, Error> {
+    let upstream_pr = config
+        .upstream(octocrab)?
+        .get_pr(&config.clone_repo, upstream_number)
+        .await?;
+
+    let span = crate::pr_span(&upstream_pr);
+    let mirror = span.in_scope(|| crate::cherry_pick_and_push_pr(repo, octocrab, upstream_pr, config, bot_info, None, None, &mut PhaseTimings::default()))?;
+
+    close_issue(octocrab, config, issue_number).await?;
+
+    return Ok(match mirror.pr_number {
+        Some(n) => format!("Retried successfully, mirrored as #{}.", n),
+        None => "Retried successfully.".to_string(),
+    });
+}
+
+async fn skip(octocrab: &Octocrab, config: &mut AppConfig, target: &Target) -> Result<String, Error> {
+    let (number, upstream_number) = match target {
+        Target::Mirror(mirror) => (mirror.pr.number, mirror.upstream_number),
+        Target::FailureIssue(number, upstream_number) => (*number, *upstream_number),
+    };
+
+    if !config.is_skipped(upstream_number) {
+        config.state.skipped_prs.push(upstream_number);
+    }
+
+    close_issue(octocrab, config, number).await?;
+
+    return Ok(format!("Upstream PR #{} won't be mirrored.", upstream_number));
+}
+
+async fn ready(octocrab: &Octocrab, mirror: &MirrorPr) -> Result<String, Error> {
+    let node_id = match &mirror.pr.node_id {
+        Some(id) => id,
+        None => return Err(Error::from("GitHub didn't give this PR a node ID.")),
+    };
+
+    if NO_NET_ACTIVITY {
+        return Ok("Marked as ready for review.".to_string());
+    }
+
+    // Drafts can only be undrafted through GraphQL.
+    let query = serde_json::json!({
+        "query": format!("mutation {{ markPullRequestReadyForReview(input: {{ pullRequestId: \"{}\" }}) {{ clientMutationId }} }}", node_id),
+    });
+    let response: serde_json::Value = octocrab.graphql(&query).await?;
+
+    if let Some(errors) = response.get("errors") {
+        return Err(Error::General(errors.to_string()));
+    }
+
+    return Ok("Marked as ready for review.".to_string());
+}
+
+/// Closes an issue or PR.
+async fn close_issue(octocrab: &Octocrab, config: &AppConfig, number: u64) -> Result<(), Error> {
+    if NO_NET_ACTIVITY {
+        return Ok(());
+    }
+
+    octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .update(number)
+        .state(IssueState::Closed)
+        .send()
+        .await?;
+
+    return Ok(());
+}
+
+async fn reply(octocrab: &Octocrab, config: &AppConfig, number: u64, message: String) {
+    if NO_NET_ACTIVITY {
+        info!("Would have replied to #{}: {}", number, message);
+        return;
+    }
+
+    let _ = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .create_comment(number, message)
+        .await
+        .inspect_err(|e| warn!("Failed to reply on #{}: {}", number, e));
+}
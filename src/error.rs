@@ -0,0 +1,271 @@
+use git2::{Error as GitError, ErrorClass, ErrorCode};
+use octocrab::Error as OctoError;
+
+#[derive(Debug)]
+pub enum Error {
+    Octocrab(OctoError),
+    /// A forge other than GitHub couldn't be reached, or answered with this status.
+    Forge { status: Option<u16>, message: String },
+    Git(GitError),
+    General(String),
+    /// The config file is missing, unreadable or invalid.
+    Config(String),
+    /// The cherry-pick left conflicts in these files.
+    Conflict(Vec<String>),
+    /// Something GitHub didn't answer in time.
+    Timeout(String),
+    /// Another error, along with where it happened.
+    Context(Box<ErrorContext>),
+}
+
+#[derive(Debug)]
+pub struct ErrorContext {
+    /// None when the error came from outside any one stage.
+    pub stage: Option<Stage>,
+    pub pr: Option<u64>,
+    pub sha: Option<String>,
+    pub source: Error,
+}
+
+/// The step of mirroring a PR an error happened in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Fetch,
+    Branch,
+    CherryPick,
+    Commit,
+    Push,
+    PrCreate,
+    Labeling,
+}
+
+/// What kind of failure an error is, which decides whether it's worth retrying.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    /// Network trouble, rate limits, or GitHub having a bad day. Likely to go away if retried.
+    Transient,
+    /// Won't go away on its own.
+    Permanent,
+    /// The cherry-pick didn't apply cleanly.
+    Conflict,
+    /// A token is invalid or lacks access.
+    Permission,
+}
+
+impl Error {
+    /// Records the stage this error happened in, unless it already knows a more specific one.
+    pub fn at(self, stage: Stage) -> Error {
+        return match self {
+            Error::Context(mut context) => {
+                context.stage = context.stage.or(Some(stage));
+                Error::Context(context)
+            }
+            source => Error::Context(Box::new(ErrorContext { stage: Some(stage), pr: None, sha: None, source })),
+        };
+    }
+
+    /// Records the upstream PR this error happened while mirroring.
+    pub fn for_pr(self, number: u64, sha: Option<&str>) -> Error {
+        return match self {
+            Error::Context(mut context) => {
+                context.pr = Some(number);
+                context.sha = sha.map(|s| s.to_string());
+                Error::Context(context)
+            }
+            source => Error::Context(Box::new(ErrorContext { stage: None, pr: Some(number), sha: sha.map(|s| s.to_string()), source })),
+        };
+    }
+
+    pub fn stage(&self) -> Option<Stage> {
+        return match self {
+            Error::Context(context) => context.stage,
+            _ => None,
+        };
+    }
+
+    /// The error without any context around it.
+    pub fn root(&self) -> &Error {
+        return match self {
+            Error::Context(context) => context.source.root(),
+            _ => self,
+        };
+    }
+
+    /// The name of the root error's variant, for labelling metrics.
+    pub fn variant(&self) -> &'static str {
+        return match self.root() {
+            Error::Octocrab(_) => "octocrab",
+            Error::Forge { .. } => "forge",
+            Error::Git(_) => "git",
+            Error::General(_) => "general",
+            Error::Config(_) => "config",
+            Error::Conflict(_) => "conflict",
+            Error::Timeout(_) => "timeout",
+            Error::Context(_) => "context",
+        };
+    }
+
+    pub fn class(&self) -> Class {
+        return match self.root() {
+            Error::Octocrab(e) => classify_octocrab(e),
+            Error::Forge { status, .. } => classify_status(*status),
+            Error::Git(e) => classify_git(e),
+            Error::Conflict(_) => Class::Conflict,
+            Error::Timeout(_) => Class::Transient,
+            Error::General(_) | Error::Config(_) | Error::Context(_) => Class::Permanent,
+        };
+    }
+
+    /// The exit code a one-shot run stopped by this error exits with.
+    ///
+    /// 1 for anything else, 2 for config problems, 3 for rejected tokens, 4 for network trouble and 5 for git failures.
+    pub fn exit_code(&self) -> i32 {
+        return match (self.root(), self.class()) {
+            (Error::Config(_), _) => 2,
+            (_, Class::Permission) => 3,
+            (_, Class::Transient) => 4,
+            (Error::Git(_) | Error::Conflict(_), _) => 5,
+            _ => 1,
+        };
+    }
+
+    /// What a maintainer can do about this error, for failure issues and logs.
+    pub fn guidance(&self) -> String {
+        return match (self.class(), self.stage()) {
+            (Class::Conflict, _) => {
+                let files = match self.root() {
+                    Error::Conflict(files) if !files.is_empty() => format!(" in `{}`", files.join("`, `")),
+                    _ => String::new(),
+                };
+                format!("The cherry-pick conflicted{}. This usually means the PR depends on changes that haven't been mirrored yet, \
+                    so mirror those first, cherry-pick this one by hand, or set `stack_mode: on_conflict` to try it on top of earlier mirrors.", files)
+            }
+            (Class::Permission, Some(Stage::Push)) => "The bot couldn't push to its fork. Check that `bot_token` is valid and can push to the fork.".to_string(),
+            (Class::Permission, Some(Stage::PrCreate | Stage::Labeling)) => "The bot couldn't open or label the PR. Check that `org_token` is valid and has write access to the target repository.".to_string(),
+            (Class::Permission, _) => "A token was rejected. Check that `org_token` and `bot_token` are valid and have access to both repositories.".to_string(),
+            (Class::Transient, _) => "This looks temporary, like network trouble or a rate limit, and still failed after retrying. Trying again later should work.".to_string(),
+            (Class::Permanent, Some(Stage::Fetch | Stage::CherryPick)) => "The merge commit couldn't be found or applied. Upstream might have been force pushed, cherry-pick this one by hand.".to_string(),
+            (Class::Permanent, Some(Stage::PrCreate)) => "GitHub refused the PR. A PR for this branch might already exist, or the branch has nothing new in it.".to_string(),
+            (Class::Permanent, _) => "This won't go away on its own, have a look at the error above.".to_string(),
+        };
+    }
+}
+
+fn classify_octocrab(error: &OctoError) -> Class {
+    return match error {
+        OctoError::GitHub { source, .. } => {
+            let status = source.status_code.as_u16();
+            let rate_limited = source.message.to_lowercase().contains("rate limit");
+            match status {
+                403 | 429 if rate_limited => Class::Transient,
+                401 | 403 => Class::Permission,
+                429 | 500..=599 => Class::Transient,
+                _ => Class::Permanent,
+            }
+        }
+        OctoError::Hyper { .. } | OctoError::Service { .. } | OctoError::Http { .. } => Class::Transient,
+        _ => Class::Permanent,
+    };
+}
+
+fn classify_status(status: Option<u16>) -> Class {
+    return match status {
+        None | Some(429 | 500..=599) => Class::Transient,
+        Some(401 | 403) => Class::Permission,
+        Some(_) => Class::Permanent,
+    };
+}
+
+fn classify_git(error: &GitError) -> Class {
+    return match (error.code(), error.class()) {
+        (ErrorCode::Auth | ErrorCode::Certificate, _) => Class::Permission,
+        (ErrorCode::Conflict | ErrorCode::MergeConflict | ErrorCode::Unmerged, _) => Class::Conflict,
+        (_, ErrorClass::Net | ErrorClass::Http | ErrorClass::Ssl | ErrorClass::Os) => Class::Transient,
+        _ => Class::Permanent,
+    };
+}
+
+impl core::fmt::Display for Stage {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let name = match self {
+            Stage::Fetch => "fetch",
+            Stage::Branch => "branch",
+            Stage::CherryPick => "cherry-pick",
+            Stage::Commit => "commit",
+            Stage::Push => "push",
+            Stage::PrCreate => "PR creation",
+            Stage::Labeling => "labeling",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+impl core::fmt::Display for Class {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let name = match self {
+            Class::Transient => "transient",
+            Class::Permanent => "permanent",
+            Class::Conflict => "conflict",
+            Class::Permission => "permission",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Error::Octocrab(e) => write!(f, "Octocrab error: {}", e),
+            Error::Forge { status: Some(status), message } => write!(f, "Forge error ({}): {}", status, message),
+            Error::Forge { status: None, message } => write!(f, "Forge error: {}", message),
+            Error::Git(e) => write!(f, "Git error: {}", e),
+            Error::General(e) => write!(f, "General error: {}", e),
+            Error::Config(e) => write!(f, "Config error: {}", e),
+            Error::Conflict(files) => write!(f, "Cherry-pick conflicted in {}", files.join(", ")),
+            Error::Timeout(e) => write!(f, "Timed out: {}", e),
+            Error::Context(c) => {
+                let stage = c.stage.map(|s| s.to_string()).unwrap_or("mirroring".to_string());
+                match (c.pr, &c.sha) {
+                    (Some(pr), Some(sha)) => write!(f, "{} failed for PR #{} ({}): {}", stage, pr, sha, c.source),
+                    (Some(pr), None) => write!(f, "{} failed for PR #{}: {}", stage, pr, c.source),
+                    _ => write!(f, "{} failed: {}", stage, c.source),
+                }
+            }
+        }
+    }
+}
+
+impl From<OctoError> for Error {
+    fn from(e: OctoError) -> Self {
+        return Error::Octocrab(e);
+    }
+}
+
+impl From<GitError> for Error {
+    fn from(e: GitError) -> Self {
+        return Error::Git(e);
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        return Error::General(e);
+    }
+}
+
+impl From<&str> for Error {
+    fn from(e: &str) -> Self {
+        return Error::General(e.to_string());
+    }
+}
+
+/// Lets results record which stage they failed in, like `git_utils::push_to_remote(..).stage(Stage::Push)?`.
+pub trait ResultExt<T> {
+    fn stage(self, stage: Stage) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn stage(self, stage: Stage) -> Result<T, Error> {
+        return self.map_err(|e| e.into().at(stage));
+    }
+}
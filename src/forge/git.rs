@@ -0,0 +1,356 @@
+use super::{rest, Forge, NewIssue, NewPr, PrPage};
+use crate::{git_utils, CommitGrouping, Error, RepoInfo};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use git2::{Commit, Delta, DiffFindOptions, FetchOptions, Oid, Patch, Repository, Sort};
+use octocrab::models::{pulls::PullRequest, repos::RepoCommit, Author};
+use serde_json::{json, Value};
+use std::{collections::HashSet, path::{Path, PathBuf}, sync::Mutex};
+
+/// The bare copy, grouping and branch head a history was worked out for.
+type HistoryKey = (PathBuf, CommitGrouping, Oid);
+
+/// The last history worked out, so PRs fetched by number during a run don't each walk the whole branch.
+static HISTORY: Mutex<Option<(HistoryKey, Vec<Oid>)>> = Mutex::new(None);
+
+/// A plain git repo, read from a bare copy kept next to the mirror's own clone. Its commits stand in for merged PRs,
+/// numbered in the order they landed on the branch so the same commit keeps the same number between runs,
+/// as long as the branch isn't force pushed.
+pub struct Git {
+    /// The bare copy of the repo.
+    path: PathBuf,
+    url: String,
+    /// What the web links in PRs start with, a `file://` URL for local paths.
+    web_url: String,
+    grouping: CommitGrouping,
+    /// The branch head the last finished run gathered, only commits after it are listed.
+    since: Option<String>,
+    /// What to log in with if the repo asks, `clone_token` or `org_token`.
+    token: String,
+}
+
+impl Git {
+    pub fn new(repo: &RepoInfo, path: PathBuf, grouping: CommitGrouping, since: Option<String>, token: String) -> Git {
+        let host = repo.host_url();
+        let web_host = match host.contains("://") {
+            true => host,
+            false => format!("file://{}", host),
+        };
+
+        return Git {
+            path,
+            url: git_utils::url_from_name(repo),
+            web_url: format!("{}/{}/{}", web_host, repo.owner, repo.name),
+            grouping,
+            since,
+            token,
+        };
+    }
+
+    /// Opens the bare copy, making it if this is the first run.
+    fn open(&self) -> Result<Repository, Error> {
+        return match Repository::open_bare(&self.path) {
+            Ok(r) => Ok(r),
+            Err(_) => Ok(Repository::init_bare(&self.path)?),
+        };
+    }
+
+    /// Brings the bare copy's branch up to date with the repo.
+    fn fetch(&self, repo: &Repository, branch: &str) -> Result<(), Error> {
+        let mut remote = repo.remote_anonymous(&self.url)?;
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(git_utils::token_callbacks(&self.token));
+        remote.fetch(&[&format!("+refs/heads/{0}:refs/heads/{0}", branch)], Some(&mut fetch_options), None)?;
+
+        return Ok(());
+    }
+
+    /// Every commit on the branch in the order it landed there, oldest first. A commit's number is its index here, plus one.
+    ///
+    /// The branch's first parents are followed, each bringing along whatever it merged in just before it, so a side branch
+    /// merged later is numbered after everything already on the branch rather than pushing those numbers along.
+    /// Going merge by merge, only the first parents are numbered.
+    ///
+    /// Every commit is looked at once, and the result is kept until the branch moves.
+    fn history(&self, repo: &Repository, branch: &str) -> Result<Vec<Oid>, Error> {
+        let head = repo.refname_to_id(&format!("refs/heads/{}", branch))?;
+        let key = (self.path.clone(), self.grouping, head);
+        if let Some((cached_key, history)) = HISTORY.lock().unwrap().as_ref() {
+            if *cached_key == key {
+                return Ok(history.clone());
+            }
+        }
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        walk.push(head)?;
+        walk.simplify_first_parent()?;
+        let first_parents: Vec<Oid> = walk.filter_map(|oid| oid.ok()).collect();
+
+        let history = match self.grouping {
+            CommitGrouping::Merge => first_parents,
+            CommitGrouping::Commit => landed_in_order(repo, &first_parents)?,
+        };
+
+        *HISTORY.lock().unwrap() = Some((key, history.clone()));
+
+        return Ok(history);
+    }
+
+    /// The commits on the branch since `since`, along with their numbers.
+    fn numbered_commits(&self, repo: &Repository, branch: &str) -> Result<Vec<(u64, Oid)>, Error> {
+        // A head the last run gathered can be gone if the branch was force pushed, then everything is listed again.
+        let since = self.since.as_deref()
+            .and_then(|s| Oid::from_str(s).ok())
+            .filter(|oid| repo.find_commit(*oid).is_ok());
+
+        let mut walk = repo.revwalk()?;
+        walk.push_ref(&format!("refs/heads/{}", branch))?;
+        if let Some(oid) = since {
+            walk.hide(oid)?;
+        }
+        let new: HashSet<Oid> = walk.filter_map(|oid| oid.ok()).collect();
+
+        let mut numbered = Vec::new();
+        for (index, oid) in self.history(repo, branch)?.into_iter().enumerate() {
+            if !new.contains(&oid) {
+                continue;
+            }
+
+            // Each of a merge's commits is mirrored on its own when going commit by commit.
+            if self.grouping == CommitGrouping::Commit && repo.find_commit(oid)?.parent_count() > 1 {
+                continue;
+            }
+
+            numbered.push((index as u64 + 1, oid));
+        }
+
+        return Ok(numbered);
+    }
+
+    /// A commit as a merged PR, shaped like GitHub's.
+    fn pull_request(&self, repo: &RepoInfo, number: u64, commit: &Commit) -> Value {
+        let sha = commit.id().to_string();
+        let author = commit.author();
+        let name = author.name().unwrap_or("Unknown");
+        let commit_url = format!("{}/commit/{}", self.web_url, sha);
+
+        let user = rest::author(&json!(0), name, None, &self.web_url, author.email());
+        let owner = rest::author(&json!(0), &repo.owner, None, &self.web_url, None);
+        let body = commit.body().unwrap_or_default().trim();
+
+        return json!({
+            "url": commit_url,
+            "id": number,
+            "html_url": commit_url,
+            "number": number,
+            "state": "closed",
+            "title": commit.summary().unwrap_or_default(),
+            "body": if body.is_empty() { Value::Null } else { json!(body) },
+            "user": user,
+            "labels": [],
+            "created_at": time(&author.when()),
+            "updated_at": time(&commit.time()),
+            "closed_at": time(&commit.time()),
+            "merged_at": time(&commit.time()),
+            "merge_commit_sha": sha,
+            "head": { "ref": repo.branch, "sha": sha },
+            "base": {
+                "ref": repo.branch,
+                "sha": commit.parent_id(0).map(|p| p.to_string()).unwrap_or_default(),
+                "repo": {
+                    "id": 0,
+                    "name": repo.name,
+                    "full_name": format!("{}/{}", repo.owner, repo.name),
+                    "owner": owner,
+                    "url": self.web_url,
+                    "html_url": self.web_url,
+                },
+            },
+        });
+    }
+
+    /// A commit along with what it changed against its first parent, shaped like GitHub's.
+    fn repo_commit(&self, repo: &Repository, commit: &Commit) -> Result<Value, Error> {
+        let parent_tree = match commit.parent(0) {
+            Ok(p) => Some(p.tree()?),
+            Err(_) => None,
+        };
+        let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let commit_url = format!("{}/commit/{}", self.web_url, commit.id());
+        let mut files = Vec::new();
+        let (mut total_additions, mut total_deletions) = (0, 0);
+        for (index, delta) in diff.deltas().enumerate() {
+            let (_, additions, deletions) = match Patch::from_diff(&diff, index)? {
+                Some(patch) => patch.line_stats()?,
+                None => (0, 0, 0),
+            };
+            total_additions += additions;
+            total_deletions += deletions;
+
+            let path = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().into_owned());
+            files.push(json!({
+                "sha": delta.new_file().id().to_string(),
+                "filename": path(delta.new_file()).or(path(delta.old_file())),
+                "status": match delta.status() {
+                    Delta::Added => "added",
+                    Delta::Deleted => "removed",
+                    Delta::Renamed => "renamed",
+                    _ => "modified",
+                },
+                "additions": additions,
+                "deletions": deletions,
+                "changes": additions + deletions,
+                "blob_url": commit_url,
+                "raw_url": commit_url,
+                "contents_url": commit_url,
+                "previous_filename": if delta.status() == Delta::Renamed { json!(path(delta.old_file())) } else { Value::Null },
+            }));
+        }
+
+        let signature = |s: git2::Signature| json!({ "name": s.name(), "email": s.email(), "date": time(&s.when()) });
+        return Ok(json!({
+            "url": commit_url,
+            "sha": commit.id().to_string(),
+            "node_id": "",
+            "html_url": commit_url,
+            "comments_url": "",
+            "commit": {
+                "url": commit_url,
+                "author": signature(commit.author()),
+                "committer": signature(commit.committer()),
+                "message": commit.message().unwrap_or_default(),
+                "comment_count": 0,
+                "tree": { "sha": commit.tree_id().to_string(), "url": commit_url },
+            },
+            // Plain git only has the names and emails of who made the commit.
+            "author": null,
+            "committer": null,
+            "parents": commit.parent_ids().map(|id| json!({ "sha": id.to_string() })).collect::<Vec<_>>(),
+            "stats": { "additions": total_additions, "deletions": total_deletions, "total": total_additions + total_deletions },
+            "files": files,
+        }));
+    }
+}
+
+#[async_trait]
+impl Forge for Git {
+    async fn current_user(&self) -> Result<Author, Error> {
+        return Err(only_a_source());
+    }
+
+    /// Everything fits on one page, which fetches the repo first.
+    async fn list_closed_prs(&self, repo: &RepoInfo, page: u32) -> Result<PrPage, Error> {
+        if page > 1 {
+            return Ok(PrPage { prs: Vec::new(), pages: Some(1) });
+        }
+
+        let local = self.open()?;
+        self.fetch(&local, &repo.branch)?;
+
+        let mut prs = Vec::new();
+        for (number, oid) in self.numbered_commits(&local, &repo.branch)? {
+            prs.push(translate(self.pull_request(repo, number, &local.find_commit(oid)?))?);
+        }
+
+        // Newest first, like the forges list them.
+        prs.reverse();
+
+        return Ok(PrPage { prs, pages: Some(1) });
+    }
+
+    async fn get_pr(&self, repo: &RepoInfo, number: u64) -> Result<PullRequest, Error> {
+        let local = self.open()?;
+        let oid = match number.checked_sub(1).and_then(|i| self.history(&local, &repo.branch).ok()?.get(i as usize).copied()) {
+            Some(o) => o,
+            None => return Err(Error::Forge { status: Some(404), message: format!("{}/{} has no commit #{} on {}", repo.owner, repo.name, number, repo.branch) }),
+        };
+
+        return translate(self.pull_request(repo, number, &local.find_commit(oid)?));
+    }
+
+    async fn get_commit(&self, _repo: &RepoInfo, sha: &str) -> Result<RepoCommit, Error> {
+        let local = self.open()?;
+        let commit = local.find_commit(Oid::from_str(sha)?)?;
+        return translate(self.repo_commit(&local, &commit)?);
+    }
+
+    async fn create_fork(&self, _repo: &RepoInfo) -> Result<String, Error> {
+        return Err(only_a_source());
+    }
+
+    async fn create_pr(&self, _repo: &RepoInfo, _pr: &NewPr) -> Result<PullRequest, Error> {
+        return Err(only_a_source());
+    }
+
+    async fn find_open_pr(&self, _repo: &RepoInfo, _head: &str) -> Result<Option<PullRequest>, Error> {
+        return Err(only_a_source());
+    }
+
+    async fn add_labels(&self, _repo: &RepoInfo, _number: u64, _labels: &[String]) -> Result<(), Error> {
+        return Err(only_a_source());
+    }
+
+    async fn create_issue(&self, _repo: &RepoInfo, _issue: &NewIssue) -> Result<String, Error> {
+        return Err(only_a_source());
+    }
+}
+
+/// Every commit reachable from the first parents, each first parent right after whatever it brought along
+/// that wasn't on the branch yet, parents before their children.
+fn landed_in_order(repo: &Repository, first_parents: &[Oid]) -> Result<Vec<Oid>, Error> {
+    let mut seen = HashSet::new();
+    let mut history = Vec::new();
+
+    for first_parent in first_parents {
+        // A commit goes back on the stack to be added once all of its parents have been.
+        let mut stack = vec![(*first_parent, false)];
+        while let Some((oid, parents_added)) = stack.pop() {
+            if parents_added {
+                history.push(oid);
+                continue;
+            }
+            if !seen.insert(oid) {
+                continue;
+            }
+
+            stack.push((oid, true));
+            let parents: Vec<Oid> = repo.find_commit(oid)?.parent_ids().collect();
+            stack.extend(parents.into_iter().rev().filter(|p| !seen.contains(p)).map(|p| (p, false)));
+        }
+    }
+
+    return Ok(history);
+}
+
+/// The head of `branch` in the bare copy at `path`, as the last fetch left it.
+pub fn fetched_head(path: &Path, branch: &str) -> Option<String> {
+    let repo = Repository::open_bare(path).ok()?;
+    let head = repo.refname_to_id(&format!("refs/heads/{}", branch)).ok()?;
+    return Some(head.to_string());
+}
+
+/// Whether `repo.branch` exists on the repo, without fetching it.
+pub fn has_branch(repo: &RepoInfo) -> Result<bool, Error> {
+    let mut remote = git2::Remote::create_detached(git_utils::url_from_name(repo).as_str())?;
+    remote.connect(git2::Direction::Fetch)?;
+    let branch = format!("refs/heads/{}", repo.branch);
+    return Ok(remote.list()?.iter().any(|head| head.name() == branch));
+}
+
+fn only_a_source() -> Error {
+    return Error::Config("A plain git repo has no PRs, issues or users, so it can only be 'clone_repo'".to_string());
+}
+
+fn time(time: &git2::Time) -> Option<DateTime<Utc>> {
+    return DateTime::from_timestamp(time.seconds(), 0);
+}
+
+fn translate<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, Error> {
+    return match serde_json::from_value(value) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(Error::General(format!("Failed to read a commit as a PR: {}", e))),
+    };
+}
@@ -0,0 +1,284 @@
+use super::{rest::{self, Rest}, Forge, NewIssue, NewPr, PrPage};
+use crate::{Error, RepoInfo};
+use async_trait::async_trait;
+use octocrab::models::{pulls::PullRequest, repos::RepoCommit, Author};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::warn;
+
+/// Gitea's default, and largest, page size.
+const PAGE_SIZE: u32 = 50;
+
+/// A Gitea or Forgejo instance, talked to over its REST API.
+pub struct Gitea {
+    rest: Rest,
+    /// The web side of the instance, for links Gitea doesn't give.
+    host_url: String,
+}
+
+impl Gitea {
+    pub fn new(repo: &RepoInfo, token: &str) -> Result<Gitea, Error> {
+        let api_url = match &repo.api_url {
+            Some(u) => u,
+            None => return Err(Error::Config(format!("{}/{} is on Gitea, so it needs an 'api_url', like https://codeberg.org/api/v1", repo.owner, repo.name))),
+        };
+
+        return Ok(Gitea {
+            rest: Rest::new("Gitea", api_url, ("Authorization", format!("token {}", token)))?,
+            host_url: repo.host_url(),
+        });
+    }
+
+    /// The IDs of the labels with these names. Gitea can only add labels that already exist, so the rest are left out.
+    async fn label_ids(&self, repo: &RepoInfo, names: &[String]) -> Result<Vec<u64>, Error> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut existing = Vec::new();
+        let mut page = 1;
+        loop {
+            let labels = self.rest.get(&format!("/repos/{}/{}/labels", repo.owner, repo.name), &[("page", page.to_string()), ("limit", PAGE_SIZE.to_string())]).await?;
+            let labels = labels.as_array().cloned().unwrap_or_default();
+            let last = labels.len() < PAGE_SIZE as usize;
+            existing.extend(labels);
+            if last {
+                break;
+            }
+            page += 1;
+        }
+
+        let mut ids = Vec::new();
+        for name in names {
+            match existing.iter().find(|l| l["name"].as_str() == Some(name.as_str())).and_then(|l| l["id"].as_u64()) {
+                Some(id) => ids.push(id),
+                None => warn!("Label '{}' doesn't exist on {}/{}, Gitea can only add existing labels.", name, repo.owner, repo.name),
+            }
+        }
+
+        return Ok(ids);
+    }
+}
+
+#[async_trait]
+impl Forge for Gitea {
+    async fn current_user(&self) -> Result<Author, Error> {
+        let user = self.rest.get("/user", &[]).await?;
+        return self.rest.translate(author(&user, &self.host_url));
+    }
+
+    async fn list_closed_prs(&self, repo: &RepoInfo, page: u32) -> Result<PrPage, Error> {
+        let query = [("state", "closed".to_string()), ("page", page.to_string()), ("limit", PAGE_SIZE.to_string())];
+        let response = self.rest.request(Method::GET, &format!("/repos/{}/{}/pulls", repo.owner, repo.name), &query, None).await?;
+
+        let pages = response
+            .headers()
+            .get("x-total-count")
+            .and_then(|t| t.to_str().ok())
+            .and_then(|t| t.parse::<u32>().ok())
+            .map(|total| total.div_ceil(PAGE_SIZE).max(1));
+
+        let mut prs = Vec::new();
+        for pr in self.rest.read(response).await?.as_array().cloned().unwrap_or_default() {
+            // Gitea can't filter by base branch.
+            if pr["base"]["ref"].as_str() == Some(repo.branch.as_str()) {
+                prs.push(self.rest.translate(pull_request(&pr, &self.host_url))?);
+            }
+        }
+
+        return Ok(PrPage { prs, pages });
+    }
+
+    async fn get_pr(&self, repo: &RepoInfo, number: u64) -> Result<PullRequest, Error> {
+        let pr = self.rest.get(&format!("/repos/{}/{}/pulls/{}", repo.owner, repo.name, number), &[]).await?;
+        return self.rest.translate(pull_request(&pr, &self.host_url));
+    }
+
+    async fn get_commit(&self, repo: &RepoInfo, sha: &str) -> Result<RepoCommit, Error> {
+        let query = [("stat", "true".to_string()), ("files", "true".to_string())];
+        let commit = self.rest.get(&format!("/repos/{}/{}/git/commits/{}", repo.owner, repo.name, sha), &query).await?;
+        return self.rest.translate(repo_commit(&commit, &self.host_url));
+    }
+
+    async fn create_fork(&self, repo: &RepoInfo) -> Result<String, Error> {
+        let fork = self.rest.post(&format!("/repos/{}/{}/forks", repo.owner, repo.name), json!({})).await?;
+
+        return match fork["clone_url"].as_str() {
+            Some(url) => Ok(url.to_string()),
+            None => Err(Error::from("Fork URL was not provided by Gitea")),
+        };
+    }
+
+    async fn create_pr(&self, repo: &RepoInfo, pr: &NewPr) -> Result<PullRequest, Error> {
+        // Gitea marks drafts by their title.
+        let title = match pr.draft {
+            true => format!("WIP: {}", pr.title),
+            false => pr.title.to_string(),
+        };
+
+        let created = self.rest.post(&format!("/repos/{}/{}/pulls", repo.owner, repo.name), json!({
+            "title": title,
+            "head": pr.head,
+            "base": pr.base,
+            "body": pr.body,
+        })).await?;
+
+        return self.rest.translate(pull_request(&created, &self.host_url));
+    }
+
+    async fn find_open_pr(&self, repo: &RepoInfo, head: &str) -> Result<Option<PullRequest>, Error> {
+        let branch = head.split_once(':').map(|(_, b)| b).unwrap_or(head);
+        let found = self.rest.get(&format!("/repos/{}/{}/pulls/{}/{}", repo.owner, repo.name, repo.branch, branch), &[]).await;
+
+        return match found {
+            Ok(p) if p["state"] == "open" => Ok(Some(self.rest.translate(pull_request(&p, &self.host_url))?)),
+            Ok(_) | Err(Error::Forge { status: Some(404), .. }) => Ok(None),
+            Err(e) => Err(e),
+        };
+    }
+
+    async fn add_labels(&self, repo: &RepoInfo, number: u64, labels: &[String]) -> Result<(), Error> {
+        let ids = self.label_ids(repo, labels).await?;
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.rest.post(&format!("/repos/{}/{}/issues/{}/labels", repo.owner, repo.name, number), json!({ "labels": ids })).await?;
+        return Ok(());
+    }
+
+    async fn create_issue(&self, repo: &RepoInfo, issue: &NewIssue) -> Result<String, Error> {
+        let labels = self.label_ids(repo, issue.labels).await?;
+
+        let created = self.rest.post(&format!("/repos/{}/{}/issues", repo.owner, repo.name), json!({
+            "title": issue.title,
+            "body": issue.body,
+            "assignees": issue.assignees,
+            "milestone": issue.milestone,
+            "labels": labels,
+        })).await?;
+
+        return match created["html_url"].as_str() {
+            Some(url) => Ok(url.to_string()),
+            None => Err(Error::from("Issue URL was not provided by Gitea")),
+        };
+    }
+}
+
+fn author(user: &Value, host_url: &str) -> Value {
+    if !user.is_object() {
+        return Value::Null;
+    }
+
+    let login = user["login"].as_str().unwrap_or_default();
+    let html_url = match user["html_url"].as_str() {
+        Some(u) => u.to_string(),
+        None => format!("{}/{}", host_url, login),
+    };
+
+    return rest::author(&user["id"], login, user["avatar_url"].as_str(), &html_url, user["email"].as_str());
+}
+
+fn repository(repo: &Value, host_url: &str) -> Value {
+    if !repo.is_object() {
+        return Value::Null;
+    }
+
+    return json!({
+        "id": repo["id"],
+        "name": repo["name"],
+        "full_name": repo["full_name"],
+        "owner": author(&repo["owner"], host_url),
+        "url": repo["html_url"],
+        "html_url": repo["html_url"],
+        "clone_url": repo["clone_url"],
+    });
+}
+
+fn pull_request(pr: &Value, host_url: &str) -> Value {
+    let branch = |b: &Value| json!({
+        "label": b["label"],
+        "ref": b["ref"],
+        "sha": b["sha"],
+        "repo": repository(&b["repo"], host_url),
+    });
+
+    let labels: Vec<Value> = pr["labels"]
+        .as_array()
+        .map(|labels| labels.iter().map(|l| json!({
+            "id": l["id"],
+            "node_id": "",
+            "url": l["url"],
+            "name": l["name"],
+            "description": l["description"],
+            "color": l["color"].as_str().unwrap_or_default().trim_start_matches('#'),
+            "default": false,
+        })).collect())
+        .unwrap_or_default();
+
+    return json!({
+        "url": pr["url"],
+        "id": pr["id"],
+        "html_url": pr["html_url"],
+        "diff_url": pr["diff_url"],
+        "patch_url": pr["patch_url"],
+        "number": pr["number"],
+        "state": pr["state"],
+        "title": pr["title"],
+        "body": pr["body"],
+        "user": author(&pr["user"], host_url),
+        "labels": labels,
+        "created_at": pr["created_at"],
+        "updated_at": pr["updated_at"],
+        "closed_at": pr["closed_at"],
+        "merged_at": pr["merged_at"],
+        "merge_commit_sha": pr["merge_commit_sha"],
+        "head": branch(&pr["head"]),
+        "base": branch(&pr["base"]),
+    });
+}
+
+fn repo_commit(commit: &Value, host_url: &str) -> Value {
+    let html_url = &commit["html_url"];
+
+    let files: Vec<Value> = commit["files"]
+        .as_array()
+        .map(|files| files.iter().map(|f| json!({
+            "sha": "",
+            "filename": f["filename"],
+            "status": match f["status"].as_str() {
+                Some("added") => "added",
+                Some("removed" | "deleted") => "removed",
+                Some("renamed") => "renamed",
+                _ => "modified",
+            },
+            "additions": 0,
+            "deletions": 0,
+            "changes": 0,
+            "blob_url": html_url,
+            "raw_url": html_url,
+            "contents_url": html_url,
+        })).collect())
+        .unwrap_or_default();
+
+    return json!({
+        "url": commit["url"],
+        "sha": commit["sha"],
+        "node_id": "",
+        "html_url": html_url,
+        "comments_url": "",
+        "commit": {
+            "url": commit["commit"]["url"],
+            "author": commit["commit"]["author"],
+            "committer": commit["commit"]["committer"],
+            "message": commit["commit"]["message"],
+            "comment_count": 0,
+            "tree": commit["commit"]["tree"],
+        },
+        "author": author(&commit["author"], host_url),
+        "committer": author(&commit["committer"], host_url),
+        "parents": commit["parents"],
+        "stats": commit["stats"],
+        "files": files,
+    });
+}
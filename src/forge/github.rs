@@ -0,0 +1,113 @@
+use super::{Forge, NewIssue, NewPr, PrPage};
+use crate::{Error, RepoInfo};
+use async_trait::async_trait;
+use octocrab::{models::{pulls::PullRequest, repos::RepoCommit, Author}, params, params::pulls::Sort, params::Direction, Octocrab, Error as OctoError};
+
+/// A GitHub client using the given token, talking to the API `repo` is on.
+pub fn github_client(repo: &RepoInfo, token: &str) -> Result<Octocrab, OctoError> {
+    let mut builder = Octocrab::builder().user_access_token(token.to_string());
+    if let Some(uri) = &repo.api_url {
+        builder = builder.base_uri(uri.as_str())?;
+    }
+
+    return builder.build();
+}
+
+#[async_trait]
+impl Forge for Octocrab {
+    async fn current_user(&self) -> Result<Author, Error> {
+        return Ok(self.current().user().await?);
+    }
+
+    async fn list_closed_prs(&self, repo: &RepoInfo, page: u32) -> Result<PrPage, Error> {
+        let mut page = self
+            .pulls(&repo.owner, &repo.name)
+            .list()
+            .sort(Sort::Created)
+            .direction(Direction::Descending)
+            .base(&repo.branch)
+            .state(params::State::Closed)
+            .per_page(100)
+            .page(page)
+            .send()
+            .await?;
+
+        return Ok(PrPage {
+            pages: page.number_of_pages(),
+            prs: page.take_items(),
+        });
+    }
+
+    async fn get_pr(&self, repo: &RepoInfo, number: u64) -> Result<PullRequest, Error> {
+        return Ok(self.pulls(&repo.owner, &repo.name).get(number).await?);
+    }
+
+    async fn get_commit(&self, repo: &RepoInfo, sha: &str) -> Result<RepoCommit, Error> {
+        return Ok(self.commits(&repo.owner, &repo.name).get(sha).await?);
+    }
+
+    async fn create_fork(&self, repo: &RepoInfo) -> Result<String, Error> {
+        let fork = self
+            .repos(&repo.owner, &repo.name)
+            .create_fork()
+            .send()
+            .await?;
+
+        return match fork.clone_url {
+            Some(url) => Ok(url.to_string()),
+            None => Err(Error::from("Fork URL was not provided by GitHub")),
+        };
+    }
+
+    async fn create_pr(&self, repo: &RepoInfo, pr: &NewPr) -> Result<PullRequest, Error> {
+        return Ok(self
+            .pulls(&repo.owner, &repo.name)
+            .create(pr.title, pr.head, pr.base)
+            .body(pr.body)
+            .draft(pr.draft)
+            .maintainer_can_modify(true)
+            .send()
+            .await?);
+    }
+
+    async fn find_open_pr(&self, repo: &RepoInfo, head: &str) -> Result<Option<PullRequest>, Error> {
+        // GitHub only filters by head when it's given as `owner:branch`.
+        let head = match head.contains(':') {
+            true => head.to_string(),
+            false => format!("{}:{}", repo.owner, head),
+        };
+
+        let mut page = self
+            .pulls(&repo.owner, &repo.name)
+            .list()
+            .state(params::State::Open)
+            .head(head)
+            .base(&repo.branch)
+            .send()
+            .await?;
+
+        return Ok(page.take_items().into_iter().next());
+    }
+
+    async fn add_labels(&self, repo: &RepoInfo, number: u64, labels: &[String]) -> Result<(), Error> {
+        self.issues(&repo.owner, &repo.name)
+            .add_labels(number, labels)
+            .await?;
+
+        return Ok(());
+    }
+
+    async fn create_issue(&self, repo: &RepoInfo, issue: &NewIssue) -> Result<String, Error> {
+        let created = self
+            .issues(&repo.owner, &repo.name)
+            .create(issue.title)
+            .body(issue.body)
+            .assignees(issue.assignees.to_vec())
+            .milestone(issue.milestone)
+            .labels(issue.labels.to_vec())
+            .send()
+            .await?;
+
+        return Ok(created.html_url.to_string());
+    }
+}
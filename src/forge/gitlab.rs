@@ -0,0 +1,326 @@
+use super::{rest::{self, Rest}, Forge, NewIssue, NewPr, PrPage};
+use crate::{Error, RepoInfo};
+use async_trait::async_trait;
+use octocrab::models::{pulls::PullRequest, repos::RepoCommit, Author};
+use reqwest::Method;
+use serde_json::{json, Value};
+use tracing::warn;
+
+pub const DEFAULT_API_URL: &str = "https://gitlab.com/api/v4";
+
+/// GitLab's largest page size.
+const PAGE_SIZE: u32 = 100;
+
+/// A GitLab instance, talked to over its REST API. Merge requests stand in for PRs, numbered by their `iid`.
+pub struct Gitlab {
+    rest: Rest,
+    /// The web side of the instance, for links GitLab doesn't give.
+    host_url: String,
+}
+
+impl Gitlab {
+    pub fn new(repo: &RepoInfo, token: &str) -> Result<Gitlab, Error> {
+        let api_url = repo.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+
+        return Ok(Gitlab {
+            rest: Rest::new("GitLab", api_url, ("PRIVATE-TOKEN", token.to_string()))?,
+            host_url: repo.host_url(),
+        });
+    }
+
+    /// GitLab needs the numeric ID of the project a merge request goes into.
+    async fn project_id(&self, repo: &RepoInfo) -> Result<u64, Error> {
+        let project = self.rest.get(&project(&repo.owner, &repo.name), &[]).await?;
+
+        return match project["id"].as_u64() {
+            Some(id) => Ok(id),
+            None => Err(Error::from(format!("GitLab didn't give the ID of {}/{}", repo.owner, repo.name))),
+        };
+    }
+
+    /// The IDs of the users with these names, GitLab assigns by ID.
+    async fn user_ids(&self, usernames: &[String]) -> Vec<u64> {
+        let mut ids = Vec::new();
+        for username in usernames {
+            let found = self.rest.get("/users", &[("username", username.clone())]).await;
+            match found.map(|users| users[0]["id"].as_u64()) {
+                Ok(Some(id)) => ids.push(id),
+                Ok(None) => warn!("GitLab user {} doesn't exist, not assigning them.", username),
+                Err(e) => warn!("Failed to look up GitLab user {}: {}", username, e),
+            }
+        }
+
+        return ids;
+    }
+
+    /// The files a commit changed, as GitHub lists them.
+    async fn commit_files(&self, repo: &RepoInfo, sha: &str) -> Result<Vec<Value>, Error> {
+        let mut files = Vec::new();
+        let mut page = 1;
+        loop {
+            let route = format!("{}/repository/commits/{}/diff", project(&repo.owner, &repo.name), sha);
+            let diffs = self.rest.get(&route, &[("page", page.to_string()), ("per_page", PAGE_SIZE.to_string())]).await?;
+            let diffs = diffs.as_array().cloned().unwrap_or_default();
+            let last = diffs.len() < PAGE_SIZE as usize;
+
+            files.extend(diffs.iter().map(|d| json!({
+                "sha": "",
+                "filename": d["new_path"],
+                "status": match (d["new_file"].as_bool(), d["deleted_file"].as_bool(), d["renamed_file"].as_bool()) {
+                    (Some(true), _, _) => "added",
+                    (_, Some(true), _) => "removed",
+                    (_, _, Some(true)) => "renamed",
+                    _ => "modified",
+                },
+                "additions": 0,
+                "deletions": 0,
+                "changes": 0,
+                "blob_url": self.host_url,
+                "raw_url": self.host_url,
+                "contents_url": self.host_url,
+                "previous_filename": d["old_path"],
+            })));
+
+            if last {
+                break;
+            }
+            page += 1;
+        }
+
+        return Ok(files);
+    }
+}
+
+#[async_trait]
+impl Forge for Gitlab {
+    async fn current_user(&self) -> Result<Author, Error> {
+        let user = self.rest.get("/user", &[]).await?;
+        return self.rest.translate(author(&user, &self.host_url));
+    }
+
+    async fn list_closed_prs(&self, repo: &RepoInfo, page: u32) -> Result<PrPage, Error> {
+        let query = [
+            ("state", "merged".to_string()),
+            ("target_branch", repo.branch.clone()),
+            ("order_by", "created_at".to_string()),
+            ("sort", "desc".to_string()),
+            ("page", page.to_string()),
+            ("per_page", PAGE_SIZE.to_string()),
+        ];
+        let response = self.rest.request(Method::GET, &format!("{}/merge_requests", project(&repo.owner, &repo.name)), &query, None).await?;
+
+        // Left out for projects with too many merge requests to count.
+        let pages = response
+            .headers()
+            .get("x-total-pages")
+            .and_then(|t| t.to_str().ok())
+            .and_then(|t| t.parse::<u32>().ok());
+
+        let mut prs = Vec::new();
+        for mr in self.rest.read(response).await?.as_array().cloned().unwrap_or_default() {
+            prs.push(self.rest.translate(merge_request(&mr, repo, &self.host_url))?);
+        }
+
+        return Ok(PrPage { prs, pages });
+    }
+
+    /// Unlike the list, a single merge request comes with how many commits it has.
+    async fn get_pr(&self, repo: &RepoInfo, number: u64) -> Result<PullRequest, Error> {
+        let route = format!("{}/merge_requests/{}", project(&repo.owner, &repo.name), number);
+        let mr = self.rest.get(&route, &[]).await?;
+
+        let response = self.rest.request(Method::GET, &format!("{}/commits", route), &[("per_page", PAGE_SIZE.to_string())], None).await?;
+        let total = response
+            .headers()
+            .get("x-total")
+            .and_then(|t| t.to_str().ok())
+            .and_then(|t| t.parse::<u64>().ok());
+        let commits = match total {
+            Some(t) => t,
+            None => self.rest.read(response).await?.as_array().map(|c| c.len() as u64).unwrap_or_default(),
+        };
+
+        let mut pr = merge_request(&mr, repo, &self.host_url);
+        pr["commits"] = json!(commits);
+        return self.rest.translate(pr);
+    }
+
+    async fn get_commit(&self, repo: &RepoInfo, sha: &str) -> Result<RepoCommit, Error> {
+        let commit = self.rest.get(&format!("{}/repository/commits/{}", project(&repo.owner, &repo.name), sha), &[]).await?;
+        let files = self.commit_files(repo, sha).await?;
+
+        let web_url = &commit["web_url"];
+        return self.rest.translate(json!({
+            "url": web_url,
+            "sha": commit["id"],
+            "node_id": "",
+            "html_url": web_url,
+            "comments_url": "",
+            "commit": {
+                "url": web_url,
+                "author": { "name": commit["author_name"], "email": commit["author_email"], "date": commit["authored_date"] },
+                "committer": { "name": commit["committer_name"], "email": commit["committer_email"], "date": commit["committed_date"] },
+                "message": commit["message"],
+                "comment_count": 0,
+                "tree": { "sha": "", "url": web_url },
+            },
+            // GitLab only has the names and emails of who made the commit, not their accounts.
+            "author": null,
+            "committer": null,
+            "parents": commit["parent_ids"].as_array().map(|ids| ids.iter().map(|id| json!({ "sha": id })).collect::<Vec<_>>()),
+            "stats": commit["stats"],
+            "files": files,
+        }));
+    }
+
+    async fn create_fork(&self, repo: &RepoInfo) -> Result<String, Error> {
+        let fork = self.rest.post(&format!("{}/fork", project(&repo.owner, &repo.name)), json!({})).await?;
+
+        return match fork["http_url_to_repo"].as_str() {
+            Some(url) => Ok(url.to_string()),
+            None => Err(Error::from("Fork URL was not provided by GitLab")),
+        };
+    }
+
+    /// Merge requests from a fork are opened on the fork, so the token needs access to it.
+    async fn create_pr(&self, repo: &RepoInfo, pr: &NewPr) -> Result<PullRequest, Error> {
+        let (source_owner, source_branch) = match pr.head.split_once(':') {
+            Some(h) => h,
+            None => (repo.owner.as_str(), pr.head),
+        };
+
+        let title = match pr.draft {
+            true => format!("Draft: {}", pr.title),
+            false => pr.title.to_string(),
+        };
+
+        let created = self.rest.post(&format!("{}/merge_requests", project(source_owner, &repo.name)), json!({
+            "source_branch": source_branch,
+            "target_branch": pr.base,
+            "target_project_id": self.project_id(repo).await?,
+            "title": title,
+            "description": pr.body,
+        })).await?;
+
+        return self.rest.translate(merge_request(&created, repo, &self.host_url));
+    }
+
+    async fn find_open_pr(&self, repo: &RepoInfo, head: &str) -> Result<Option<PullRequest>, Error> {
+        let source_branch = head.split_once(':').map(|(_, b)| b).unwrap_or(head);
+        let query = [
+            ("state", "opened".to_string()),
+            ("source_branch", source_branch.to_string()),
+            ("target_branch", repo.branch.clone()),
+        ];
+        let mrs = self.rest.get(&format!("{}/merge_requests", project(&repo.owner, &repo.name)), &query).await?;
+
+        return match mrs.as_array().and_then(|m| m.first()) {
+            Some(mr) => Ok(Some(self.rest.translate(merge_request(mr, repo, &self.host_url))?)),
+            None => Ok(None),
+        };
+    }
+
+    async fn add_labels(&self, repo: &RepoInfo, number: u64, labels: &[String]) -> Result<(), Error> {
+        if labels.is_empty() {
+            return Ok(());
+        }
+
+        self.rest.put(&format!("{}/merge_requests/{}", project(&repo.owner, &repo.name), number), json!({ "add_labels": labels.join(",") })).await?;
+        return Ok(());
+    }
+
+    async fn create_issue(&self, repo: &RepoInfo, issue: &NewIssue) -> Result<String, Error> {
+        let created = self.rest.post(&format!("{}/issues", project(&repo.owner, &repo.name)), json!({
+            "title": issue.title,
+            "description": issue.body,
+            "assignee_ids": self.user_ids(issue.assignees).await,
+            "milestone_id": issue.milestone,
+            "labels": issue.labels.join(","),
+        })).await?;
+
+        return match created["web_url"].as_str() {
+            Some(url) => Ok(url.to_string()),
+            None => Err(Error::from("Issue URL was not provided by GitLab")),
+        };
+    }
+}
+
+/// The API route of a project, which can be in nested groups.
+fn project(owner: &str, name: &str) -> String {
+    return format!("/projects/{}%2F{}", owner.replace('/', "%2F"), name);
+}
+
+fn author(user: &Value, host_url: &str) -> Value {
+    if !user.is_object() {
+        return Value::Null;
+    }
+
+    let login = user["username"].as_str().unwrap_or_default();
+    let html_url = match user["web_url"].as_str() {
+        Some(u) => u.to_string(),
+        None => format!("{}/{}", host_url, login),
+    };
+    let email = user["public_email"].as_str().or(user["email"].as_str());
+
+    return rest::author(&user["id"], login, user["avatar_url"].as_str(), &html_url, email);
+}
+
+fn merge_request(mr: &Value, repo: &RepoInfo, host_url: &str) -> Value {
+    let web_url = mr["web_url"].as_str().unwrap_or_default();
+
+    // Fast-forwarded merge requests have no merge commit, so their head stands in. Mirroring fails them if they have more than one commit.
+    let sha = [&mr["merge_commit_sha"], &mr["squash_commit_sha"], &mr["sha"]]
+        .into_iter()
+        .find(|s| s.is_string())
+        .cloned()
+        .unwrap_or_default();
+
+    let labels: Vec<Value> = mr["labels"]
+        .as_array()
+        .map(|labels| labels.iter().map(|name| json!({
+            "id": 0,
+            "node_id": "",
+            "url": web_url,
+            "name": name,
+            "color": "ededed",
+            "default": false,
+        })).collect())
+        .unwrap_or_default();
+
+    let owner = rest::author(&json!(0), &repo.owner, None, &format!("{}/{}", host_url, repo.owner), None);
+    let html_url = format!("{}/{}/{}", host_url, repo.owner, repo.name);
+
+    return json!({
+        "url": web_url,
+        "id": mr["id"],
+        "html_url": web_url,
+        "diff_url": format!("{}.diff", web_url),
+        "patch_url": format!("{}.patch", web_url),
+        "commits_url": format!("{}/commits", web_url),
+        "number": mr["iid"],
+        "state": if mr["state"] == "opened" { "open" } else { "closed" },
+        "title": mr["title"],
+        "body": mr["description"],
+        "user": author(&mr["author"], host_url),
+        "labels": labels,
+        "created_at": mr["created_at"],
+        "updated_at": mr["updated_at"],
+        "closed_at": mr["closed_at"],
+        "merged_at": mr["merged_at"],
+        "merge_commit_sha": if mr["merged_at"].is_null() { Value::Null } else { sha },
+        "draft": mr["draft"],
+        "head": { "ref": mr["source_branch"], "sha": mr["sha"].as_str().unwrap_or_default() },
+        "base": {
+            "ref": mr["target_branch"],
+            "sha": mr["diff_refs"]["base_sha"].as_str().unwrap_or_default(),
+            "repo": {
+                "id": mr["target_project_id"],
+                "name": repo.name,
+                "full_name": format!("{}/{}", repo.owner, repo.name),
+                "owner": owner,
+                "url": html_url,
+                "html_url": html_url,
+            },
+        },
+    });
+}
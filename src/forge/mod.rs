@@ -0,0 +1,91 @@
+//! The calls the mirror makes to the forges the repos live on, so upstream and the target can each be on GitHub, Gitea/Forgejo or GitLab.
+//! Upstream can also be a plain git repo, whose commits stand in for PRs.
+
+use crate::{Error, RepoInfo};
+use async_trait::async_trait;
+use octocrab::models::{pulls::PullRequest, repos::RepoCommit, Author};
+
+pub mod git;
+mod gitea;
+mod github;
+mod gitlab;
+mod rest;
+
+pub use github::github_client;
+pub use gitlab::DEFAULT_API_URL as GITLAB_API_URL;
+
+/// Which kind of forge a repo is on.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForgeKind {
+    #[default]
+    Github,
+    /// Gitea, or Forgejo, which has the same API.
+    Gitea,
+    /// GitLab, where merge requests stand in for PRs.
+    Gitlab,
+    /// A plain git repo or local path without PRs, only as `clone_repo`. Its commits stand in for merged PRs.
+    Git,
+}
+
+/// A page of closed PRs into a repo's branch, newest first.
+pub struct PrPage {
+    pub prs: Vec<PullRequest>,
+    /// How many pages there are in total, if the forge said.
+    pub pages: Option<u32>,
+}
+
+pub struct NewPr<'a> {
+    pub title: &'a str,
+    /// The branch to merge, as `owner:branch`.
+    pub head: &'a str,
+    pub base: &'a str,
+    pub body: &'a str,
+    pub draft: bool,
+}
+
+pub struct NewIssue<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    pub assignees: &'a [String],
+    pub milestone: Option<u64>,
+    pub labels: &'a [String],
+}
+
+/// PRs, commits, forks and issues on a forge. Models are GitHub's, other forges translate their answers into them.
+#[async_trait]
+pub trait Forge: Send + Sync {
+    /// The user the token belongs to.
+    async fn current_user(&self) -> Result<Author, Error>;
+
+    /// One page of closed PRs into `repo.branch`, starting at 1.
+    async fn list_closed_prs(&self, repo: &RepoInfo, page: u32) -> Result<PrPage, Error>;
+
+    async fn get_pr(&self, repo: &RepoInfo, number: u64) -> Result<PullRequest, Error>;
+
+    /// A commit, along with the files it changed and how many lines.
+    async fn get_commit(&self, repo: &RepoInfo, sha: &str) -> Result<RepoCommit, Error>;
+
+    /// Forks `repo` into the token's account, returning the fork's clone URL.
+    async fn create_fork(&self, repo: &RepoInfo) -> Result<String, Error>;
+
+    async fn create_pr(&self, repo: &RepoInfo, pr: &NewPr) -> Result<PullRequest, Error>;
+
+    /// The open PR from `head` into `repo.branch`, if there is one. Tells whether a PR create that failed went through anyway.
+    async fn find_open_pr(&self, repo: &RepoInfo, head: &str) -> Result<Option<PullRequest>, Error>;
+
+    async fn add_labels(&self, repo: &RepoInfo, number: u64, labels: &[String]) -> Result<(), Error>;
+
+    /// Opens an issue, returning its URL.
+    async fn create_issue(&self, repo: &RepoInfo, issue: &NewIssue) -> Result<String, Error>;
+}
+
+/// A client for the forge `repo` is on, using `token`.
+pub fn connect(repo: &RepoInfo, token: &str) -> Result<Box<dyn Forge>, Error> {
+    return match repo.forge {
+        ForgeKind::Github => Ok(Box::new(github_client(repo, token)?)),
+        ForgeKind::Gitea => Ok(Box::new(gitea::Gitea::new(repo, token)?)),
+        ForgeKind::Gitlab => Ok(Box::new(gitlab::Gitlab::new(repo, token)?)),
+        ForgeKind::Git => Err(Error::Config(format!("{}/{} is a plain git repo, which can only be 'clone_repo'", repo.owner, repo.name))),
+    };
+}
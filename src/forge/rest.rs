@@ -0,0 +1,113 @@
+//! Talking to the JSON APIs of forges octocrab doesn't cover, and reshaping their answers into GitHub's models.
+
+use crate::Error;
+use reqwest::{Method, Response};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+pub struct Rest {
+    client: reqwest::Client,
+    api_url: String,
+    /// The header the token goes in, and its value.
+    auth: (&'static str, String),
+    /// The forge's name, for errors.
+    name: &'static str,
+}
+
+impl Rest {
+    pub fn new(name: &'static str, api_url: &str, auth: (&'static str, String)) -> Result<Rest, Error> {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(30)).build() {
+            Ok(c) => c,
+            Err(e) => return Err(Error::General(format!("Failed to set up a {} client: {}", name, e))),
+        };
+
+        return Ok(Rest {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            auth,
+            name,
+        });
+    }
+
+    pub async fn request(&self, method: Method, route: &str, query: &[(&str, String)], body: Option<&Value>) -> Result<Response, Error> {
+        let mut request = self.client
+            .request(method, format!("{}{}", self.api_url, route))
+            .header(self.auth.0, &self.auth.1)
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = match request.send().await {
+            Ok(r) => r,
+            Err(e) => return Err(Error::Forge { status: None, message: e.to_string() }),
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        // Gitea says what went wrong in 'message', GitLab in either 'message' or 'error', sometimes as an object.
+        let message = match response.json::<Value>().await.unwrap_or_default() {
+            Value::Object(body) => match body.get("message").or(body.get("error")) {
+                Some(Value::String(m)) => m.clone(),
+                Some(m) => m.to_string(),
+                None => Value::Object(body).to_string(),
+            },
+            _ => status.canonical_reason().unwrap_or_default().to_string(),
+        };
+
+        return Err(Error::Forge { status: Some(status.as_u16()), message });
+    }
+
+    pub async fn get(&self, route: &str, query: &[(&str, String)]) -> Result<Value, Error> {
+        return self.read(self.request(Method::GET, route, query, None).await?).await;
+    }
+
+    pub async fn post(&self, route: &str, body: Value) -> Result<Value, Error> {
+        return self.read(self.request(Method::POST, route, &[], Some(&body)).await?).await;
+    }
+
+    pub async fn put(&self, route: &str, body: Value) -> Result<Value, Error> {
+        return self.read(self.request(Method::PUT, route, &[], Some(&body)).await?).await;
+    }
+
+    pub async fn read(&self, response: Response) -> Result<Value, Error> {
+        return match response.json().await {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::Forge { status: None, message: format!("{}'s response couldn't be read: {}", self.name, e) }),
+        };
+    }
+
+    /// Reads one of GitHub's models out of an answer that was reshaped to look like GitHub's.
+    pub fn translate<T: DeserializeOwned>(&self, value: Value) -> Result<T, Error> {
+        return match serde_json::from_value(value) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::Forge { status: None, message: format!("{}'s response wasn't understood: {}", self.name, e) }),
+        };
+    }
+}
+
+/// A user shaped like GitHub's.
+pub fn author(id: &Value, login: &str, avatar_url: Option<&str>, html_url: &str, email: Option<&str>) -> Value {
+    let mut author = json!({
+        "login": login,
+        "id": id,
+        "node_id": "",
+        "avatar_url": avatar_url.unwrap_or(html_url),
+        "gravatar_id": "",
+        "type": "User",
+        "site_admin": false,
+        "email": email.filter(|e| !e.is_empty()),
+    });
+
+    // Other forges have nothing like most of GitHub's user links, but they can't be left out.
+    for key in ["url", "html_url", "followers_url", "following_url", "gists_url", "starred_url", "subscriptions_url",
+        "organizations_url", "repos_url", "events_url", "received_events_url"] {
+        author[key] = json!(html_url);
+    }
+
+    return author;
+}
@@ -1,92 +1,237 @@
-use crate::{Error, AppConfig};
+use crate::{forge::{self, ForgeKind}, logging, Error, AppConfig, RepoInfo, ResultExt, Stage};
 use chrono::Local;
 use git2::{Error as GitError, self, build::*, Progress, *}; // Progress needs to be explicitly imported here since it conflicts with one in 'build::'
-use octocrab::{models::Author, OctocrabBuilder};
-use std::{cell::RefCell, io::{self, stdout, Write}, path::{Path, PathBuf}};
+use octocrab::models::Author;
+use std::{cell::RefCell, fs, io::{self, stdout, Write}, path::{Path, PathBuf}};
 use tokio::task::block_in_place;
+use tracing::{debug, info, warn};
 
 const PR_REMOTE_NAME: &str = "upstream";
 const COPY_REMOTE_NAME: &str = "cloned";
 const PUSH_REMOTE_NAME: &str = "origin";
 
-/// Pushes the current branch to the owned remote with the same branch name.
+/// Pushes the current branch to the owned remote with the same branch name, the bot's fork or `into_repo` itself.
 pub fn push_to_remote(repo: &Repository, config: &AppConfig, bot_info: &Author) -> Result<(), Error> {
-    if crate::NO_NET_ACTIVITY {
-        return Ok(());
+    let branch = repo.head()?.shorthand().unwrap_or_default().to_string();
+
+    // Mirror branches are owned by the bot, so regenerating one should overwrite it.
+    push(repo, config, bot_info, &[format!("+refs/heads/{}:refs/heads/{}", branch, branch)])?;
+
+    info!("Pushing to {} from refs/heads/{}", PUSH_REMOTE_NAME, branch);
+
+    return Ok(());
+}
+
+/// Callbacks that log in to a remote with `token`. Forges take a token as the password whatever the user name is,
+/// so the one in the URL is used if there is one.
+pub fn token_callbacks(token: &str) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |_, username, _| {
+        return git2::Cred::userpass_plaintext(username.unwrap_or("git"), token);
+    });
+
+    return callbacks;
+}
+
+/// Points `into_repo.branch` on the bot's fork at the target repo's, so the fork doesn't fall behind.
+pub fn sync_fork(repo: &Repository, config: &AppConfig, bot_info: &Author) -> Result<(), Error> {
+    let branch = &config.into_repo.branch;
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|_, _, _| {
+        return git2::Cred::userpass_plaintext(&bot_info.login, &config.org_token);
+    });
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    if let Some(depth) = config.fetch_depth() {
+        fetch_options.depth(depth as i32);
     }
 
-    let mut remote = repo.find_remote(PUSH_REMOTE_NAME)?;
+    let tracking = format!("refs/remotes/{}/{}", PR_REMOTE_NAME, branch);
+    repo.find_remote(PR_REMOTE_NAME)?
+        .fetch(&[&format!("+refs/heads/{}:{}", branch, tracking)], Some(&mut fetch_options), None)?;
 
-    {
-        let mut stdout = stdout().lock();
+    push(repo, config, bot_info, &[format!("+{}:refs/heads/{}", tracking, branch)])?;
 
-        let mut remote_callbacks = RemoteCallbacks::new();
-        remote_callbacks.credentials(|_, _, _| {
-            println!("Attempting to authenticate");
+    info!("Synced {} on the bot's fork with {}/{}", branch, config.into_repo.owner, config.into_repo.name);
+
+    return Ok(());
+}
+
+/// Deletes these mirror branches from where they were pushed, skipping any that are already gone. Returns how many were deleted.
+///
+/// In `into_repo` itself only branches with the mirror branch prefix are touched, and the target branch never is.
+pub fn delete_mirror_branches(repo: &Repository, config: &AppConfig, bot_info: &Author, branches: &[String]) -> Result<usize, Error> {
+    let existing: Vec<String> = {
+        let mut remote = repo.find_remote(PUSH_REMOTE_NAME)?;
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(|_, _, _| {
             return git2::Cred::userpass_plaintext(&bot_info.login, &config.bot_token);
         });
+        let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+        let heads = connection.list()?.iter().map(|head| head.name().to_string()).collect();
+        heads
+    };
 
-        remote_callbacks.push_update_reference(|_, opt| {
-            if opt.is_some() {
-                // I don't know why I'm fabricating an error here or why it's needed...
-                return Err(GitError::new(ErrorCode::Ambiguous, ErrorClass::Callback, format!("Failed to push! {:?}", opt)));
-            }
-            return Ok(());
-        });
+    let refspecs: Vec<String> = branches
+        .iter()
+        .filter(|b| **b != config.into_repo.branch && b.starts_with(config.branch_prefix()))
+        .map(|b| format!("refs/heads/{}", b))
+        .filter(|r| existing.contains(r))
+        .map(|r| format!(":{}", r))
+        .collect();
 
-        remote_callbacks.push_transfer_progress(|arg1, arg2, arg3| {
-            print!("Pushing: {}/{}: {}\r", arg1, arg2, arg3);
-            std::io::stdout().flush().unwrap();
-        });
+    if refspecs.is_empty() {
+        return Ok(0);
+    }
 
-        remote_callbacks.pack_progress(|arg1, arg2, arg3| {
-            _ = write!(stdout, "Packing-{:?}: {}/{}\r", arg1, arg2, arg3);
-            std::io::stdout().flush().unwrap();
-        });
+    push(repo, config, bot_info, &refspecs)?;
 
-        let mut push_options = git2::PushOptions::new();
-        push_options.remote_callbacks(remote_callbacks);
+    return Ok(refspecs.len());
+}
 
-        remote.push(
-            &[&format!("refs/heads/{}:refs/heads/{}",
-                repo.head()?.shorthand().unwrap_or_default(),
-                repo.head()?.shorthand().unwrap_or_default())],
-            Some(&mut push_options),
-        )?;
+/// Pushes to the bot's fork, or `into_repo` when pushing there directly, with the bot's credentials.
+fn push(repo: &Repository, config: &AppConfig, bot_info: &Author, refspecs: &[String]) -> Result<(), Error> {
+    if crate::NO_NET_ACTIVITY {
+        return Ok(());
     }
 
-    println!("Pushing to {} from refs/heads/{}",
-        PUSH_REMOTE_NAME, repo.head()?.shorthand().unwrap_or_default());
+    let mut remote = repo.find_remote(PUSH_REMOTE_NAME)?;
+    let mut stdout = stdout().lock();
+
+    let mut remote_callbacks = RemoteCallbacks::new();
+    remote_callbacks.credentials(|_, _, _| {
+        debug!("Attempting to authenticate");
+        return git2::Cred::userpass_plaintext(&bot_info.login, &config.bot_token);
+    });
+
+    remote_callbacks.push_update_reference(|_, opt| {
+        if opt.is_some() {
+            // I don't know why I'm fabricating an error here or why it's needed...
+            return Err(GitError::new(ErrorCode::Ambiguous, ErrorClass::Callback, format!("Failed to push! {:?}", opt)));
+        }
+        return Ok(());
+    });
+
+    remote_callbacks.push_transfer_progress(|arg1, arg2, arg3| {
+        if !logging::show_progress() {
+            return;
+        }
+
+        print!("Pushing: {}/{}: {}\r", arg1, arg2, arg3);
+        let _ = std::io::stdout().flush();
+    });
+
+    remote_callbacks.pack_progress(|arg1, arg2, arg3| {
+        if !logging::show_progress() {
+            return;
+        }
+
+        _ = write!(stdout, "Packing-{:?}: {}/{}\r", arg1, arg2, arg3);
+        let _ = std::io::stdout().flush();
+    });
+
+    let mut push_options = git2::PushOptions::new();
+    push_options.remote_callbacks(remote_callbacks);
+
+    remote.push(refspecs, Some(&mut push_options))?;
 
     return Ok(());
 }
 
-pub fn cherry_pick_commit(repo: &Repository, config: &AppConfig, bot_info: &Author, sha: &str) -> Result<(), Error> {
-    {
-        let state = RefCell::new(State::default());
+/// Fetches the given commits from upstream in one go, so they can be cherry-picked.
+///
+/// With a `fetch_depth` only each commit and its parents are fetched. If upstream won't hand out commits by SHA,
+/// the latest `clone_repo.branch` is fetched instead.
+pub fn fetch_upstream(repo: &Repository, config: &AppConfig, shas: &[&str]) -> Result<(), Error> {
+    if !shas.is_empty() {
+        let fetched = fetch_from_upstream(repo, shas, config.fetch_depth().map(|_| 2));
+        match fetched {
+            Ok(()) => return Ok(()),
+            Err(e) => warn!("Failed to fetch {} commits by SHA, fetching all of {} instead: {}", shas.len(), config.clone_repo.branch, e),
+        }
+    }
 
-        // Handles the progress of the fetch.
-        let mut fetch_callback = RemoteCallbacks::new();
-        fetch_callback.transfer_progress(|stats| {
-            let mut state = state.borrow_mut();
-            state.progress = Some(stats.to_owned());
-            print(&mut *state);
-            let _ = stdout().flush();
-            return true;
-        });
+    return fetch_from_upstream(repo, &[&config.clone_repo.branch], config.fetch_depth());
+}
 
-        // Options relating to the fetch.
-        let mut fetch_options = FetchOptions::new();
-        fetch_options
-            .update_fetchhead(true)
-            // .depth(1)
-            .remote_callbacks(fetch_callback);
+/// Fetches every commit a run will cherry-pick before it starts, returning the PRs whose commits still couldn't be found.
+///
+/// Commits already in the local repo aren't fetched again. Any that `fetch_upstream` doesn't bring in,
+/// like the heads of PRs that were rebased away, are tried once more through the PRs' own refs.
+pub fn fetch_for_run(repo: &Repository, config: &AppConfig, prs: &[(u64, &str)]) -> Result<Vec<u64>, Error> {
+    let missing = |prs: &[(u64, &str)]| -> Vec<(u64, String)> {
+        return prs.iter().filter(|(_, sha)| !has_commit(repo, sha)).map(|(n, sha)| (*n, sha.to_string())).collect();
+    };
+
+    let needed = missing(prs);
+    if needed.is_empty() {
+        info!("All {} upstream commits were already fetched.", prs.len());
+        return Ok(Vec::new());
+    }
 
-        let mut remote = repo.find_remote(&COPY_REMOTE_NAME)?;
+    let shas: Vec<&str> = needed.iter().map(|(_, sha)| sha.as_str()).collect();
+    fetch_upstream(repo, config, &shas)?;
 
-        remote.fetch(&[&config.clone_repo.branch], Some(&mut fetch_options), None)?;
+    let still_missing: Vec<(u64, &str)> = needed.iter().filter(|(_, sha)| !has_commit(repo, sha)).map(|(n, sha)| (*n, sha.as_str())).collect();
+    let refspecs: Vec<String> = still_missing.iter().filter_map(|(number, _)| pr_refspec(&config.clone_repo, *number)).collect();
+    if !refspecs.is_empty() {
+        info!("{} commits weren't fetched with the rest, fetching them through their PRs.", refspecs.len());
+        let refspecs: Vec<&str> = refspecs.iter().map(|r| r.as_str()).collect();
+        if let Err(e) = fetch_from_upstream(repo, &refspecs, config.fetch_depth()) {
+            warn!("Failed to fetch PR refs from upstream: {}", e);
+        }
     }
 
+    return Ok(missing(&still_missing).into_iter().map(|(number, _)| number).collect());
+}
+
+/// Fetches a PR's own ref, which forges keep around after it's merged. Plain git repos have no such thing.
+fn pr_refspec(repo_info: &RepoInfo, number: u64) -> Option<String> {
+    let source = match repo_info.forge {
+        ForgeKind::Github | ForgeKind::Gitea => format!("refs/pull/{}/head", number),
+        ForgeKind::Gitlab => format!("refs/merge-requests/{}/head", number),
+        ForgeKind::Git => return None,
+    };
+
+    return Some(format!("+{}:refs/remotes/{}/pull/{}", source, COPY_REMOTE_NAME, number));
+}
+
+fn fetch_from_upstream(repo: &Repository, refspecs: &[&str], depth: Option<u32>) -> Result<(), Error> {
+    let state = RefCell::new(State::default());
+
+    // Handles the progress of the fetch.
+    let mut fetch_callback = RemoteCallbacks::new();
+    fetch_callback.transfer_progress(|stats| {
+        let mut state = state.borrow_mut();
+        state.progress = Some(stats.to_owned());
+        print(&mut *state);
+        let _ = stdout().flush();
+        return true;
+    });
+
+    // Options relating to the fetch.
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .update_fetchhead(true)
+        .remote_callbacks(fetch_callback);
+    if let Some(depth) = depth {
+        fetch_options.depth(depth as i32);
+    }
+
+    let mut remote = repo.find_remote(&COPY_REMOTE_NAME)?;
+
+    remote.fetch(refspecs, Some(&mut fetch_options), None)?;
+
+    return Ok(());
+}
+
+/// Whether a commit is in the local repo, so it doesn't need fetching.
+pub fn has_commit(repo: &Repository, sha: &str) -> bool {
+    return Oid::from_str(sha).and_then(|oid| repo.find_commit(oid)).is_ok();
+}
+
+pub fn cherry_pick_commit(repo: &Repository, config: &AppConfig, bot_info: &Author, sha: &str) -> Result<(), Error> {
     let commit = repo.find_commit(git2::Oid::from_str(sha)?)?;
 
     repo.checkout_index(None, None)?;
@@ -109,24 +254,42 @@ pub fn cherry_pick_commit(repo: &Repository, config: &AppConfig, bot_info: &Auth
         .checkout_builder(checkout_builder)
         .merge_opts(merge_opts);
 
+    // Merges are picked against the branch they were merged into.
+    if commit.parent_count() > 1 {
+        cherrypick_options.mainline(1);
+    }
+
     repo.cherrypick(&commit, Some(&mut cherrypick_options))?;
 
     // repo.merge(&[&commit], Some(&mut merge_opts), Some(&mut checkout_builder))?;
 
-    // Commit the changes.
+    let index = repo.index()?;
+    if index.has_conflicts() {
+        let files = index
+            .conflicts()?
+            .filter_map(|c| c.ok())
+            .filter_map(|c| c.our.or(c.their).or(c.ancestor))
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .collect();
+        return Err(Error::Conflict(files));
+    }
+
+    return commit_cherry_pick(repo, config, bot_info, &commit, sha).stage(Stage::Commit);
+}
+
+/// Commits a finished cherry-pick, keeping the original author.
+fn commit_cherry_pick(repo: &Repository, config: &AppConfig, bot_info: &Author, commit: &Commit, sha: &str) -> Result<(), Error> {
     {
         let now = Local::now();
         let commit_time = Time::new(now.timestamp(), now.offset().local_minus_utc() / 60);
 
         let commit_sig = Signature::new(&commit.author().name().unwrap_or("Unkown"),
             &commit.author().email().unwrap_or("Unkown"),
-            &commit.time(),)
-            .expect("Failed to create author signature");
+            &commit.time(),)?;
 
         let auth_sig = Signature::new(&bot_info.login,
             &bot_info.email.to_owned().unwrap_or("Unknown".to_string()),
-            &commit_time,)
-            .expect("Failed to create committer signature");
+            &commit_time,)?;
 
         let msg = format!("Cherry-picked commit {} from {}/{}/{}",
             sha, config.clone_repo.owner, config.clone_repo.name, config.clone_repo.branch);
@@ -141,10 +304,12 @@ pub fn cherry_pick_commit(repo: &Repository, config: &AppConfig, bot_info: &Auth
     Ok(())
 }
 
-/// Creates and checksout to a new branch with the given name.
-pub fn create_branch(repo: &Repository, branch_name: &str) -> Result<(), Error> {
-    let head = repo.head()?;
-    let head_commit = head.peel_to_commit()?;
+/// Creates and checksout to a new branch with the given name, starting from `base` if given or HEAD otherwise.
+pub fn create_branch(repo: &Repository, branch_name: &str, base: Option<Oid>) -> Result<(), Error> {
+    let head_commit = match base {
+        Some(oid) => repo.find_commit(oid)?,
+        None => repo.head()?.peel_to_commit()?,
+    };
     // let head_tree = head_commit.tree()?;
     let branch = repo.branch(branch_name, &head_commit, true)?;
     let branch_ref = branch.into_reference();
@@ -166,23 +331,23 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
 
     let repo = match Repository::open(&path) {
         Ok(repo) => {
-            println!("Opened existing repo");
+            info!("Opened existing repo");
             repo
         }
         Err(_) => {
-            println!("Failed to open existing repo at {}, attempting to create a new one", path);
+            info!("Failed to open existing repo at {}, attempting to create a new one", path);
             return block_in_place(|| setup_new_repo(&config, &path));
         }
     };
 
-    println!("Accessed repo at {}", path);
+    info!("Accessed repo at {}", path);
 
     let state = RefCell::new(State::default());
 
     {
         repo.set_head(&format!("refs/heads/{}", into_repo_info.branch))?;
 
-        // Fetches the latest changes from the upstream repo and pushes them to the owned fork.
+        // Fetches the latest changes from the upstream repo, `sync_fork` pushes them to the owned fork.
         {
             // Handles the progress of the fetch.
             let mut callback = RemoteCallbacks::new();
@@ -194,36 +359,19 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
                 return true;
             });
             callback.credentials(|_, _, _| {
-                println!("Attempting to authenticate");
+                debug!("Attempting to authenticate");
                 return git2::Cred::userpass_plaintext(&botinfo.login, &config.org_token);
             });
             // Options relating to the fetch.
             let mut fetch_options = FetchOptions::new();
             fetch_options.update_fetchhead(true)
-                // .depth(1)
                 .remote_callbacks(callback);
+            if let Some(depth) = config.fetch_depth() {
+                fetch_options.depth(depth as i32);
+            }
 
             let mut fork_remote = repo.find_remote(&PR_REMOTE_NAME)?;
             fork_remote.fetch(&[&into_repo_info.branch], Some(&mut fetch_options), None)?;
-
-            // let mut callback = RemoteCallbacks::new(); //TODO: This shouldn't be commented out.
-            // callback.transfer_progress(|stats| {
-            //     let mut state = state.borrow_mut();
-            //     state.progress = Some(stats.to_owned());
-            //     print(&mut *state);
-            //     let _ = stdout().flush();
-            //     return true;
-            // });
-            // callback.credentials(|_, _, _| {
-            //     println!("Attempting to authenticate");
-            //     return git2::Cred::userpass_plaintext(&BOT_USERNAME, &config.api_token);
-            // });
-            // // Options relating to the push.
-            // let mut push_options = PushOptions::new();
-            // push_options.remote_callbacks(callback);
-
-            // let mut push_remote = repo.find_remote(&PUSH_REMOTE_NAME)?;
-            // push_remote.push(&[&into_repo_info.branch], Some(&mut push_options))?;
         }
 
         let fetch_head = repo.find_reference("FETCH_HEAD")?;
@@ -234,7 +382,7 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
         if analysis.0.is_up_to_date() {
             drop(fetch_head);
             drop(remote_commit_ref);
-            println!("Already up to date");
+            info!("Already up to date");
             return Ok(repo);
         }
 
@@ -242,7 +390,7 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
 
         local_branch.set_target(remote_commit_ref.id(), "")?;
 
-        repo.set_head(local_branch.name().unwrap())?;
+        repo.set_head(local_branch.name().ok_or("Branch name isn't valid UTF-8")?)?;
     }
 
     // Handles the progress of the checkout, and ensuring that the checkout occurs.
@@ -259,7 +407,7 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
         });
 
     repo.checkout_head(Some(&mut checkout_builder))?;
-    println!("Checked out head");
+    info!("Checked out head");
 
     return Ok(repo);
 }
@@ -268,36 +416,31 @@ pub fn ensure_repo(config: &AppConfig, botinfo: &Author) -> Result<Repository, E
 #[tokio::main]
 async fn setup_new_repo(config: &AppConfig, path: &String) -> Result<Repository, Error> {
     let upstream_repo_info = &config.into_repo;
-    let remote_url = url_from_name(&upstream_repo_info.owner, &upstream_repo_info.name);
-    let clone_url = url_from_name(&config.clone_repo.owner, &config.clone_repo.name);
+    let remote_url = url_from_name(upstream_repo_info);
+    let clone_url = url_from_name(&config.clone_repo);
     // let owned_url = &config.owned_url;
 
-    // Start by forking the upstream repo.
+    // Start by forking the upstream repo, unless mirror branches are pushed straight into it.
     //? I don't love dragging all the Octocrab stuff into this as I wanted to keep it localised to main,
     //? but this needs to happen upon the creation of a new repo, which main doesn't know anything about.
     //? This is the cleanest solution for now.
 
     // Lots of .expects below this point, but I doubt any of them will happen in a typical situation.
 
-    let octocrab = OctocrabBuilder::new()
-        .user_access_token(config.bot_token.clone())
-        .build()?;
-
-    let fork = octocrab
-        .repos(&config.into_repo.owner, &config.into_repo.name)
-        .create_fork()
-        .send()
-        .await?;
-
-    let fork_url = match fork.clone_url {
-        Some(url) => url,
-        None => {
-            return Err(Error::from("Fork URL was not provided by GitHub"))
+    let push_url = match config.pushes_to_fork() {
+        true => {
+            let fork_url = forge::connect(&config.into_repo, &config.bot_token)?
+                .create_fork(&config.into_repo)
+                .await?;
+            info!("Using fork at {}", fork_url.as_str());
+            fork_url
+        }
+        false => {
+            info!("Pushing mirror branches straight to {}", remote_url);
+            remote_url.clone()
         }
     };
 
-    println!("Using fork at {}", fork_url.as_str());
-
     //TODO: This doesn't seem to be needed.
     // Wait a few seconds for the fork to be created.
     // println!("Waiting to ensure fork to be created...");
@@ -334,28 +477,34 @@ async fn setup_new_repo(config: &AppConfig, path: &String) -> Result<Repository,
     // Options relating to the fetch.
     let mut fetch_options = FetchOptions::new();
     fetch_options
-        // .depth(1)
         .update_fetchhead(true)
         .remote_callbacks(fetch_callback);
+    if let Some(depth) = config.fetch_depth() {
+        info!("Cloning only the last {} commits", depth);
+        fetch_options.depth(depth as i32);
+    }
 
-    println!("Cloning repo locally");
+    info!("Cloning repo locally");
     // Clones the repo.
     let repo = RepoBuilder::new()
         // .branch(&upstream_repo_info.branch)
         .fetch_options(fetch_options)
         .with_checkout(checkout_builder)
-        .clone(&fork_url.as_str(), Path::new(&path))?;
+        .clone(&push_url.as_str(), Path::new(&path))?;
 
     // Adds the required remotes.
     repo.remote(PR_REMOTE_NAME, &remote_url)?;
     repo.remote(COPY_REMOTE_NAME, &clone_url)?;
 
-    println!("\nForked and cloned new repo");
+    info!("Cloned new repo");
 
     return Ok(repo);
 }
 
 pub fn reset_repo(repo: &Repository, config: &AppConfig) -> Result<(), Error> {
+    // A failed cherry-pick leaves the repo mid-operation.
+    repo.cleanup_state()?;
+
     let mut checkout_options = CheckoutBuilder::new();
     checkout_options.force();
 
@@ -370,19 +519,130 @@ pub fn reset_repo(repo: &Repository, config: &AppConfig) -> Result<(), Error> {
         }
 
         if branch.delete().is_err() {
-            eprintln!("Failed to delete branch {}", branch.name()?.unwrap_or_default());
+            warn!("Failed to delete branch {}", branch.name()?.unwrap_or_default());
         }
     }
 
     Ok(())
 }
 
-fn url_from_name(owner: &str, name: &str) -> String {
-    return format!("https://github.com/{}/{}", owner, name);
+/// Checks the local repo over before a run, fixing whatever a crash could have left it in, and returns what was repaired.
+///
+/// A repo that can't be opened or fixed is deleted, so `ensure_repo` clones it again.
+pub fn repair_repo(config: &AppConfig) -> Result<Vec<String>, Error> {
+    let path = config.get_repo_path();
+    if !Path::new(&path).exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut repairs = Vec::new();
+    let problem = match Repository::open(&path) {
+        Ok(repo) => match repair(&repo, config, &mut repairs) {
+            Ok(()) => return Ok(repairs),
+            Err(e) => e.to_string(),
+        },
+        Err(e) => e.to_string(),
+    };
+
+    warn!("The local repo at {} can't be repaired, it will be cloned again: {}", path, problem);
+    fs::remove_dir_all(&path).map_err(|e| Error::General(format!("Failed to delete the broken repo at {}: {}", path, e)))?;
+    repairs.push(format!("deleted the repo to clone it again: {}", problem));
+
+    return Ok(repairs);
+}
+
+fn repair(repo: &Repository, config: &AppConfig, repairs: &mut Vec<String>) -> Result<(), Error> {
+    repair_remotes(repo, config, repairs)?;
+
+    let state = repo.state();
+    if state != RepositoryState::Clean {
+        repo.cleanup_state()?;
+        repairs.push(format!("aborted an unfinished {:?}", state));
+    }
+
+    if repo.head_detached()? {
+        repo.set_head(&format!("refs/heads/{}", config.into_repo.branch))?;
+        repairs.push(format!("reattached the detached HEAD to {}", config.into_repo.branch));
+    }
+
+    if repo.index().and_then(|mut index| index.read(true)).is_err() {
+        let index_path = repo.path().join("index");
+        fs::remove_file(&index_path).map_err(|e| Error::General(format!("Failed to delete the corrupted index at {}: {}", index_path.display(), e)))?;
+        repairs.push("rebuilt the corrupted index".to_string());
+    }
+
+    let mut status_options = StatusOptions::new();
+    status_options.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut status_options))?;
+    let untracked: Vec<String> = statuses.iter().filter(|s| s.status().is_wt_new()).filter_map(|s| s.path().map(str::to_string)).collect();
+    let changed = statuses.iter().any(|s| !s.status().is_wt_new());
+    drop(statuses);
+
+    if changed {
+        let mut checkout_options = CheckoutBuilder::new();
+        checkout_options.force();
+        repo.reset(repo.head()?.peel_to_commit()?.as_object(), ResetType::Hard, Some(&mut checkout_options))?;
+        repairs.push("threw away uncommitted changes".to_string());
+    }
+
+    if !untracked.is_empty() {
+        let workdir = repo.workdir().ok_or("The local repo has no working directory")?;
+        for file in &untracked {
+            // Nested repos show up as a single untracked directory.
+            let path = workdir.join(file);
+            let removed = match path.is_dir() {
+                true => fs::remove_dir_all(&path),
+                false => fs::remove_file(&path),
+            };
+            removed.map_err(|e| Error::General(format!("Failed to delete untracked file {}: {}", file, e)))?;
+        }
+        repairs.push(format!("deleted {} untracked files", untracked.len()));
+    }
+
+    return Ok(());
+}
+
+/// Makes sure the remotes point where the config says. The fork can't be found again without forking, so a missing `origin` needs a new clone.
+fn repair_remotes(repo: &Repository, config: &AppConfig, repairs: &mut Vec<String>) -> Result<(), Error> {
+    let target_url = url_from_name(&config.into_repo);
+    let origin_url = match config.pushes_to_fork() {
+        true => None,
+        false => Some(target_url.clone()),
+    };
+
+    for (name, url) in [(PR_REMOTE_NAME, Some(target_url.clone())), (COPY_REMOTE_NAME, Some(url_from_name(&config.clone_repo))), (PUSH_REMOTE_NAME, origin_url)] {
+        let current = repo.find_remote(name).ok().map(|r| r.url().unwrap_or_default().to_string());
+        match (current, url) {
+            (Some(current), Some(url)) if current != url => {
+                repo.remote_set_url(name, &url)?;
+                repairs.push(format!("pointed remote '{}' at {} instead of {}", name, url, current));
+            }
+            (None, Some(url)) => {
+                repo.remote(name, &url)?;
+                repairs.push(format!("added the missing remote '{}'", name));
+            }
+            (Some(current), None) if current == target_url => {
+                return Err(Error::General(format!("remote '{}' points at {} rather than the bot's fork", name, current)));
+            }
+            (None, None) => return Err(Error::General(format!("remote '{}' is missing", name))),
+            _ => {}
+        }
+    }
+
+    return Ok(());
+}
+
+/// Where `repo` is cloned from, a URL or a local path.
+pub fn url_from_name(repo: &RepoInfo) -> String {
+    return format!("{}/{}/{}", repo.host_url(), repo.owner, repo.name);
 }
 
 // Copied from the example docs, just prints a Git-style progress bar when cloning or fetching.
 fn print(state: &mut State) {
+    if !logging::show_progress() {
+        return;
+    }
+
     let stats = state.progress.as_ref();
     if stats.is_none() {
         print!("Receiving objects: {:4}/{:4} {}\r",
@@ -393,12 +653,13 @@ fn print(state: &mut State) {
                 .as_ref()
                 .map(|s| s.to_string_lossy().into_owned())
                 .unwrap_or_default());
-        io::stdout().flush().unwrap();
+        let _ = io::stdout().flush();
         return;
     }
     let stats = stats.unwrap();
-    let network_pct = (100 * stats.received_objects()) / stats.total_objects();
-    let index_pct = (100 * stats.indexed_objects()) / stats.total_objects();
+    let total_objects = stats.total_objects().max(1);
+    let network_pct = (100 * stats.received_objects()) / total_objects;
+    let index_pct = (100 * stats.indexed_objects()) / total_objects;
     let co_pct = if state.total > 0 {
         (100 * state.current) / state.total
     } else {
@@ -436,7 +697,7 @@ fn print(state: &mut State) {
                 .unwrap_or_default()
         )
     }
-    io::stdout().flush().unwrap();
+    let _ = io::stdout().flush();
 }
 
 #[derive(Default)]
@@ -0,0 +1,140 @@
+use crate::{AppConfig, NO_NET_ACTIVITY};
+use octocrab::{models::{pulls::PullRequest, repos::RepoCommit}, Octocrab};
+use regex::Regex;
+use tracing::{error, info, warn};
+
+/// The colour given to created labels that don't come from an upstream label, such as size labels.
+const DEFAULT_COLOR: &str = "ededed";
+
+/// A label the bot wants on a mirror PR, and the colour to create it with if it doesn't exist yet.
+struct WantedLabel {
+    name: String,
+    color: String,
+    description: String,
+    /// Copied straight from upstream, so only wanted if the target repo already has it.
+    copy_only: bool,
+}
+
+/// Works out which target repo labels a mirror of the given upstream PR should get, according to `label_map`.
+///
+/// Labels missing from the target repo are created if `label_map.create_missing` is set, and skipped otherwise.
+pub async fn translate_labels(octocrab: &Octocrab, config: &AppConfig, upstream_pr: &PullRequest, merge_commit: Option<&RepoCommit>) -> Vec<String> {
+    let label_map = &config.label_map;
+    let mut wanted: Vec<WantedLabel> = Vec::new();
+
+    let rules: Vec<(Regex, &str)> = label_map.rules
+        .iter()
+        .filter_map(|rule| match rule.regex() {
+            Ok(r) => Some((r, rule.to.as_str())),
+            Err(e) => {
+                warn!("Ignoring invalid label rule for '{}': {}", rule.to, e);
+                None
+            }
+        })
+        .collect();
+
+    for label in upstream_pr.labels.iter().flatten() {
+        for (regex, to) in &rules {
+            if let Some(captures) = regex.captures(&label.name) {
+                let mut name = String::new();
+                captures.expand(to, &mut name);
+                push_label(&mut wanted, name, &label.color, label.description.as_deref().unwrap_or_default(), false);
+            }
+        }
+
+        if label_map.copy_existing {
+            push_label(&mut wanted, label.name.clone(), &label.color, label.description.as_deref().unwrap_or_default(), true);
+        }
+    }
+
+    if let Some(size) = size_label(config, merge_commit) {
+        push_label(&mut wanted, size, DEFAULT_COLOR, "", false);
+    }
+
+    if wanted.is_empty() {
+        return Vec::new();
+    }
+
+    let existing = match get_target_labels(octocrab, config).await {
+        Some(l) => l,
+        None => return wanted.into_iter().filter(|l| !l.copy_only).map(|l| l.name).collect(),
+    };
+
+    let mut labels = Vec::new();
+    for label in wanted {
+        if existing.contains(&label.name) {
+            labels.push(label.name);
+            continue;
+        }
+
+        if label.copy_only || !label_map.create_missing {
+            continue;
+        }
+
+        if create_label(octocrab, config, &label).await {
+            labels.push(label.name);
+        }
+    }
+
+    return labels;
+}
+
+fn push_label(wanted: &mut Vec<WantedLabel>, name: String, color: &str, description: &str, copy_only: bool) {
+    if name.is_empty() {
+        return;
+    }
+
+    if let Some(existing) = wanted.iter_mut().find(|l| l.name == name) {
+        existing.copy_only &= copy_only;
+        return;
+    }
+
+    wanted.push(WantedLabel { name, color: color.to_string(), description: description.to_string(), copy_only });
+}
+
+/// Picks the first size label big enough for the upstream merge commit's changes.
+fn size_label(config: &AppConfig, merge_commit: Option<&RepoCommit>) -> Option<String> {
+    let stats = merge_commit?.stats.as_ref()?;
+    let changed = stats.additions.unwrap_or_default() + stats.deletions.unwrap_or_default();
+
+    return config.label_map.size_labels
+        .iter()
+        .find(|s| s.max_lines.is_none_or(|max| changed <= max))
+        .map(|s| s.label.clone());
+}
+
+async fn get_target_labels(octocrab: &Octocrab, config: &AppConfig) -> Option<Vec<String>> {
+    if !config.target_on_github() {
+        return None;
+    }
+
+    let issues = octocrab.issues(&config.into_repo.owner, &config.into_repo.name);
+
+    let labels = match issues.list_labels_for_repo().per_page(100).send().await {
+        Ok(first_page) => octocrab.all_pages(first_page).await,
+        Err(e) => Err(e),
+    };
+
+    return match labels {
+        Ok(l) => Some(l.into_iter().map(|l| l.name).collect()),
+        Err(e) => {
+            warn!("Failed to get labels of {}/{}: {}", config.into_repo.owner, config.into_repo.name, e);
+            None
+        }
+    };
+}
+
+async fn create_label(octocrab: &Octocrab, config: &AppConfig, label: &WantedLabel) -> bool {
+    info!("Creating label '{}' on {}/{}.", label.name, config.into_repo.owner, config.into_repo.name);
+
+    if NO_NET_ACTIVITY {
+        return true;
+    }
+
+    return octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .create_label(&label.name, &label.color, &label.description)
+        .await
+        .inspect_err(|e| error!("Failed to create label '{}': {}", label.name, e))
+        .is_ok();
+}
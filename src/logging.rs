@@ -0,0 +1,76 @@
+use crate::{metrics, AppConfig, LogFormat, LogRotation};
+use std::{io::IsTerminal, sync::atomic::{AtomicBool, Ordering}};
+use tracing_appender::{non_blocking::WorkerGuard, rolling::{RollingFileAppender, Rotation}};
+use tracing::Level;
+use tracing_subscriber::{filter::Targets, fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry};
+
+/// The target octocrab logs its requests under, which the API call metric counts.
+const OCTOCRAB_TARGET: &str = "octocrab";
+
+/// The prefix of the log files written to `logging.directory`.
+const LOG_FILE_PREFIX: &str = "mirror_bot";
+
+/// Whether git progress bars should be drawn, which would only get in the way of JSON or piped output.
+static SHOW_PROGRESS: AtomicBool = AtomicBool::new(true);
+
+/// Sets up logging according to `logging`.
+///
+/// The returned guard flushes the log file when dropped, so it has to live as long as the program does.
+/// `RUST_LOG` overrides the configured level if it's set.
+pub fn init(config: &AppConfig) -> Option<WorkerGuard> {
+    let settings = &config.logging;
+
+    let level = match &settings.level {
+        Some(l) => l.clone(),
+        None if config.debug.unwrap_or(false) => "debug".to_string(),
+        None => "info".to_string(),
+    };
+
+    // Filters are per layer so the API call metric still sees octocrab's requests when they aren't being logged.
+    let filter = || EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}={}", env!("CARGO_CRATE_NAME"), level)));
+
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = vec![
+        format_layer(settings.format, std::io::stdout, std::io::stdout().is_terminal()).with_filter(filter()).boxed(),
+        metrics::ApiCallLayer.with_filter(Targets::new().with_target(OCTOCRAB_TARGET, Level::DEBUG)).boxed(),
+    ];
+
+    let guard = match &settings.directory {
+        Some(directory) => {
+            let rotation = match settings.rotation {
+                LogRotation::Minutely => Rotation::MINUTELY,
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+                LogRotation::Never => Rotation::NEVER,
+            };
+
+            let (writer, guard) = tracing_appender::non_blocking(RollingFileAppender::new(rotation, directory, LOG_FILE_PREFIX));
+            layers.push(format_layer(settings.format, writer, false).with_filter(filter()).boxed());
+            Some(guard)
+        }
+        None => None,
+    };
+
+    SHOW_PROGRESS.store(settings.format == LogFormat::Human && std::io::stdout().is_terminal(), Ordering::Relaxed);
+
+    if let Err(e) = tracing_subscriber::registry().with(layers).try_init() {
+        eprintln!("Failed to set up logging: {}", e);
+    }
+
+    return guard;
+}
+
+fn format_layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    return match format {
+        LogFormat::Human => fmt::layer().with_ansi(ansi).with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer().json().with_current_span(true).with_span_list(true).with_ansi(ansi).with_writer(writer).boxed(),
+    };
+}
+
+/// Whether git progress bars should be drawn.
+pub fn show_progress() -> bool {
+    return SHOW_PROGRESS.load(Ordering::Relaxed);
+}
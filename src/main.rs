@@ -1,14 +1,38 @@
 use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
-use clokwerk::{Interval, Job, Scheduler, TimeUnits};
+use clokwerk::{Interval, Scheduler, TimeUnits};
 use futures::executor::block_on;
-use git2::{Error as GitError, Repository};
-use octocrab::{self, models::pulls::PullRequest, models::Author, params, params::pulls::Sort, params::Direction, Octocrab, Error as OctoError};
+use git2::{Oid, Repository};
+use octocrab::{self, models::pulls::PullRequest, models::repos::RepoCommit, models::Author, Octocrab};
 use serde_yaml;
-use std::{fs, io::Write, path::Path, thread::sleep, time::Duration};
+use std::{cell::Cell, fs, path::{Path, PathBuf}, thread::sleep, time::{Duration, Instant}};
 use tokio::time::timeout;
-
+use error::{Class, Error, ResultExt, Stage};
+use forge::{Forge, ForgeKind, NewIssue, NewPr};
+use mirrors::MirrorList;
+use report::{Decision, Phase, PhaseTimings, RunReport};
+use state::State;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+mod assignees;
+mod commands;
+mod error;
+mod forge;
 mod git_utils;
+mod labels;
+mod logging;
+mod metrics;
+mod mirrors;
+mod notify;
 mod pr_template;
+mod reconcile;
+mod reload;
+mod report;
+mod reverts;
+mod state;
+mod validate;
+
+#[cfg(test)]
+mod tests;
 
 #[allow(dead_code)]
 const COW: &str = "((...))\n( o o )\n \\   / \n  ^_^  ";
@@ -27,331 +51,816 @@ const CHERRY_PICK_ONLY: [u64; 0] = [];
 
 // The template used to generate the YAML file when the application is first run.
 const YAML_TEMPLATE: &str = "\
-                                ### NOTE THAT THIS FILE WILL BE ALTERED\n\n### The bot uses this file to store per-run data, and regenerates it every run.\n\
-                                ### The information you enter will be used and remembered, but do not rely on it being static.\n\n\
+                                ### The bot never writes to this file, so it can be kept in version control.\n\
+                                ### What the bot remembers between runs, like how far it got, is kept in the state file instead (see 'state_file').\n\
+                                ### Run the bot with `validate` to check this file, or `schema` for a JSON Schema editors can use.\n\n\
                                 # Yes, this bot requires two access tokens, one owned by the account and one owned by the organization.\n\
                                 # Yes, this blows. Talk to Github about it\n\
                                 ## The GitHub access token owned by the organization.\norg_token: token-here\n\
                                 ## The GitHub access token owned by the bot user account.\nbot_token: token-here\n\
-                                ## The repo we'll be cloning PRs from\nclone_repo:\n  ## The owner or org of the repository\n  owner: space-wizards\n  ## The name of the repository\n  name: space-station-14\n  ## The branch to check for PRs on\n  branch: master\n\
-                                ## The repo we'll be making our PR to\ninto_repo:\n  ## The owner or org of the repository to clone PRs into\n  owner: Simple-Station\n  ## The name of the repository to clone PRs into\n  name: Parkstation\n  ## The branch to clone PRs into\n  branch: master\n\
-                                ## The date to start checking for PRs from\n## Note that if this is too low, you'll get *every PR ever made*. This will be a lot of PRs. Format is YYYY-MM-DD\ndate_from: 2006-06-17\n\
+                                ## A token to read 'clone_repo' with, for when it's on a different host than 'into_repo'. Leave this out to use 'org_token'\n# clone_token: token-here\n\
+                                ## The repo we'll be cloning PRs from\nclone_repo:\n  ## The owner or org of the repository\n  owner: space-wizards\n  ## The name of the repository\n  name: space-station-14\n  ## The branch to check for PRs on\n  branch: master\n  ## 'github', 'gitea' for Gitea and Forgejo, or 'gitlab', where merge requests are mirrored like PRs\n  ## 'git' reads a plain git repository without PRs from 'git_host', which can also be a local directory, and mirrors its commits instead\n  forge: github\n  ## The API and git host the repository is on, for GitHub Enterprise Server, Gitea or self-hosted GitLab. Leave these out to use github.com, or gitlab.com for GitLab\n  ## Gitea needs 'api_url', like https://codeberg.org/api/v1, and Gitea and GitLab clone from the same host as their API\n  # api_url: https://github.example.com/api/v3\n  # git_host: https://github.example.com\n\
+                                ## The repo we'll be making our PR to\ninto_repo:\n  ## The owner or org of the repository to clone PRs into\n  owner: Simple-Station\n  ## The name of the repository to clone PRs into\n  name: Parkstation\n  ## The branch to clone PRs into\n  branch: master\n  ## The same as for 'clone_repo', the bot's fork is made on this host too\n  ## Reviewers, milestones and assignees on PRs, closing reverted mirrors, reconciling and commands only work on GitHub\n  ## On GitLab, merge requests are opened on the bot's fork, so 'org_token' needs access to it\n  forge: github\n  # api_url: https://github.example.com/api/v3\n  # git_host: https://github.example.com\n\
+                                ## The date to start checking for PRs from, until a run finishes and the state file remembers how far it got\n## Note that if this is too low, you'll get *every PR ever made*. This will be a lot of PRs. Format is YYYY-MM-DD\ndate_from: 2006-06-17\n\
                                 ## The number of days between checks for new PRs\n## '7' would run once a week\n## A value of '0' will run once before exiting\ndays_between: 7\n\
                                 ## A list of labels to apply to PRs made by the bot\npr_labels: [ ]\n\
                                 ## A list of labels to apply to Issues made by the bot\nissue_labels: [ ]\n\
+                                ## How upstream labels are translated into labels on mirror PRs, on top of 'pr_labels'\nlabel_map:\n  ## Each rule maps upstream labels matching either an exact name ('from') or a regex ('pattern') onto a label ('to'). Regex captures can be used like '$1'\n  ## e.g. - { from: 'Changes: UI', to: 'UI' } or - { pattern: '^Area: (.*)$', to: 'A: $1' }\n  rules: [ ]\n  ## Whether to copy upstream labels that the target repo also has\n  copy_existing: false\n  ## Whether to create labels that the rules map onto but the target repo doesn't have, with the upstream label's colour\n  create_missing: false\n  ## Labels based on the number of added and deleted lines, the first one with a big enough 'max_lines' is used, leave 'max_lines' out to catch everything else\n  ## e.g. - { max_lines: 10, label: 'Size: XS' }\n  size_labels: [ ]\n\
+                                ## Settings for the PRs and Issues made by the bot\npr_settings:\n  ## Whether PRs are opened as drafts\n  draft: true\n  ## Users and teams to request reviews on PRs from\n  reviewers: [ ]\n  team_reviewers: [ ]\n  ## Users to assign to PRs and Issues\n  assignees: [ ]\n  ## 'static' assigns everyone listed, 'round_robin' assigns one listed user at a time taking turns,\n  ## and 'codeowners' assigns the owners of the changed files according to the target repo's CODEOWNERS file, falling back on the list\n  assignee_mode: static\n  ## The number of the milestone to put PRs and Issues in\n  milestone: null\n\
                                 ## A list of labels to ignore PRs with\n## If a PR has any of these labels, it won't be mirrored\nignored_labels: [ ]\n\
                                 ## A list of users to ignore PRs from\nignored_users: [ 'github-actions[bot]' ]\n\
                                 ## The page number to stop collecting PRs at. This is in groups of 100, sorted by when they were created. \n## If this is empty, we'll get every PR every ever made and check their merge date.\n\
                                 ## Note that setting this to only get the first page or two *is not necessarily the best idea*, since an earlier-made PR could be merged *after* a later-made one, and thus missing them is possible.\n\
-                                ## This is to avoid gathering thousands of PRs you know you will never want.\nhard_cap: 0\
+                                ## This is to avoid gathering thousands of PRs you know you will never want.\nhard_cap: 0\n\
+                                ## How mirror branches are based on each other, for when upstream PRs depend on earlier ones that haven't been merged here yet.\n\
+                                ## 'never' bases every mirror on the target branch, 'always' bases every mirror on the previous one,\n\
+                                ## and 'on_conflict' only retries on top of the previous mirror if the cherry-pick fails.\n\
+                                ## Stacked mirror PRs will say which mirror PR they depend on, merge them in order.\nstack_mode: never\n\
+                                ## How commits from a plain git 'clone_repo' are grouped into mirror PRs. 'commit' mirrors every commit on its own,\n\
+                                ## and 'merge' follows the branch's first parents, mirroring each merge as a whole. Each is numbered in the order it landed on the branch, which force pushing the branch can change\n# commit_grouping: commit\n\
+                                ## What to do when upstream reverts a PR that was already mirrored.\n\
+                                ## 'mirror' mirrors the revert and links it to the original mirror PR,\n\
+                                ## 'close' closes the original mirror PR with a comment instead if it hasn't been merged yet.\nrevert_action: mirror\n\
+                                ## Whether to check up on every mirror PR made by the bot after each run, reporting which were merged, closed, or went stale\nreconcile: false\n\
+                                ## The number of days an open mirror PR can go untouched before it's reported as stale\nstale_after_days: 14\n\
+                                ## Whether to delete the branches of merged and closed mirror PRs from wherever they were pushed at the start of each run. Only works on GitHub\n\
+                                ## The fork's copy of 'into_repo.branch' is always kept in sync with the target repo's\ncleanup_branches: true\n\
+                                ## Where mirror branches are pushed. 'fork' pushes them to the bot's fork of 'into_repo', and 'same_repo' pushes them straight into 'into_repo',\n\
+                                ## so PR workflows there get the repo's secrets. 'bot_token' needs write access to 'into_repo' for 'same_repo'\npush_mode: fork\n\
+                                ## What the names of mirror branches pushed straight into 'into_repo' start with. Only branches starting with it are ever cleaned up\n# branch_prefix: mirror/\n\
+                                ## Adding this label to a stale or closed mirror PR will have the bot re-mirror it from the latest target branch as a new PR during reconciliation\nremirror_label: remirror\n\
+                                ## Whether maintainers with write access can command the bot by commenting on its mirror PRs and failure issues\n\
+                                ## '/rebase' recreates a mirror PR on the latest target branch, '/ready' takes a mirror PR out of draft,\n\
+                                ## '/retry' attempts a failed mirror again, and '/skip' stops an upstream PR from ever being mirrored\ncommands: false\n\
+                                ## The number of minutes between checks for new commands\ncommand_poll_minutes: 10\n\
+                                ## Upstream PRs that will never be mirrored, '/skip' adds to a separate list in the state file\nskipped_prs: [ ]\n\
+                                ## Where the bot keeps what it remembers between runs. Delete it to start over from 'date_from'\nstate_file: simple_mirror_state.json\n\
+                                ## The directory the local clone is kept in. Leave this out to use the current directory\n# repo_dir: repos\n\
+                                ## How many commits deep to clone and fetch, for huge repositories. Upstream commits are then fetched by SHA along with only their parents\n\
+                                ## The bot's fork should be kept close to 'into_repo' when this is used, or pushing may need history the clone doesn't have. Leave this out to get the full history\n# fetch_depth: 50\n\
+                                ## How many times to retry mirroring a PR that failed for a temporary reason, like network trouble or a rate limit, waiting longer each time\ntransient_retries: 2\n\
+                                ## A directory to write a JSON and a Markdown report of every run to, for archiving and comparing runs. Leave this out to not write reports\n# report_dir: reports\n\
+                                ## An address to serve Prometheus metrics on at '/metrics', and a health check at '/healthz', while running on a schedule. Leave this out to not serve them\n# metrics_address: 127.0.0.1:9090\n\
+                                ## Where to send messages when a run finishes and when a PR fails to mirror. 'kind' is one of 'discord', 'slack', 'matrix' or 'webhook' (a JSON POST)\n\
+                                ## 'events' picks from 'run' and 'failure', and 'run_template' and 'failure_template' override the messages. Run messages can use {mirrored}, {failed}, {skipped} and {links},\n\
+                                ## failure messages can use {number}, {title}, {error}, {upstream_url} and {issue_url}, and both can use {clone_repo} and {into_repo}\n\
+                                ## e.g. - { kind: discord, url: 'https://discord.com/api/webhooks/...', events: [ failure ] }\n\
+                                ## or - { kind: matrix, url: 'https://matrix.org', room: '!room:matrix.org', token: token-here }\nnotifications: [ ]\n\
+                                ## Where and how the bot logs\nlogging:\n  ## One of 'trace', 'debug', 'info', 'warn' or 'error'. Leaving this out uses 'debug' if 'debug' is set, and 'info' otherwise. RUST_LOG overrides this\n  # level: info\n  ## 'human' for readable lines, or 'json' for one JSON object per line\n  format: human\n  ## A directory to also write log files to, leave this out to only log to the console\n  # directory: logs\n  ## How often to start a new log file, one of 'minutely', 'hourly', 'daily' or 'never'\n  rotation: daily\
                             ";
 
 #[tokio::main]
 async fn main() {
-    let config = generate_config();
-
-    if config.days_between == 0 {
-        let config = generate_config();
-
-        let octocrab = octocrab::OctocrabBuilder::new()
-            .user_access_token(config.org_token.clone())
-            .build()
-            .expect("Octocrab failed to build");
+    match std::env::args().nth(1).as_deref() {
+        Some("validate") => std::process::exit(validate::run().await),
+        Some("schema") => {
+            println!("{}", validate::schema());
+            return;
+        }
+        Some(other) => {
+            eprintln!("Unknown command '{}', expected 'validate' or 'schema', or nothing to run the bot.", other);
+            std::process::exit(Error::Config(String::new()).exit_code());
+        }
+        None => {}
+    }
 
-        let bot_info = block_on(get_bot_info(&config));
+    let config = match generate_config() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    let log_guard = logging::init(&config);
 
-        println!("'days_between' is set to 0, running once then exiting.");
-        mirror_prs(&octocrab, &config, &bot_info).await; //? Completely circumvents the scheduling and file writing all together.
+    if config.days_between == 0 {
+        info!("'days_between' is set to 0, running once then exiting.");
+        if let Err(e) = run_once(&config).await {
+            error!("{}", e);
+            drop(log_guard);
+            std::process::exit(e.exit_code());
+        }
         return;
     }
 
-    if config.date_from_with_time().and_utc() >= Utc::now() {
+    if let Some(address) = &config.metrics_address {
+        metrics::serve(address);
+    }
+
+    let first_run = if config.cursor() >= Utc::now() {
         //FIXME: This isn't comparing correctly I guess??
-        println!("'date_from' is set to a date in the future ({}), the mirror will first run {} days after that point.", config.date_from_with_time(), config.days_between);
-        // Create a task that runs once at the configured date_from plus the days_between, then repeates every days_between thereafter.
-        let cur_time = Utc::now();
-        let first_run = config
-            .date_from_with_time()
-            .and_utc()
-            .checked_add_days(config.days_between_days())
-            .expect("Are your dates and times valid?");
-        let until_first_run = (first_run - cur_time).num_days() as u32; // Fucking *needs* to be u32.
-        let until_first_run_int = until_first_run.days();
-
-        println!("First run will be at {}, in {} days.", first_run.naive_local(), until_first_run);
-        println!("This program will now loop indefinitely. It should obviously be run in the background.");
-
-        let mut prime_scheduler = Scheduler::with_tz(Utc);
-        prime_scheduler
-            .every(until_first_run_int)
-            .once()
-            .run(move || loop_schedules(setup_tasks(config.clone())));
+        info!("'date_from' is set to a date in the future ({}), the mirror will first run {} days after that point.", config.cursor(), config.days_between);
+        // Wait until the configured date_from plus the days_between, then run every days_between thereafter.
+        let first_run = match config.cursor().checked_add_days(config.days_between_days()) {
+            Some(t) => t,
+            None => {
+                let e = Error::Config("'date_from' plus 'days_between' is out of range, are your dates and times valid?".to_string());
+                error!("{}", e);
+                drop(log_guard);
+                std::process::exit(e.exit_code());
+            }
+        };
+
+        info!("First run will be at {}, in {} days.", first_run.naive_local(), (first_run - Utc::now()).num_days());
+        metrics::set_next_run(first_run);
+        Some(first_run)
     } else {
-        println!("Running mirror now and setting up repeating task to run every {} days.", config.days_between);
-        println!("This program will now loop indefinitely. It should obviously be run in the background.");
+        info!("Running mirror now and setting up repeating task to run every {} days.", config.days_between);
+        None
+    };
 
-        run_tasks();
-        loop_schedules(setup_tasks(config));
-    }
+    info!("This program will now loop indefinitely. It should obviously be run in the background.");
+    info!("Changes to {} are picked up without restarting.", FILE_NAME);
+
+    reload::set(config);
+    loop_schedules(first_run);
 }
 
-fn loop_schedules(mut scheduler: Scheduler<Utc>) {
+/// Runs the scheduled tasks forever, starting at `first_run` if it's given and right away otherwise.
+/// Changes to the config file are picked up as they're made, rebuilding the schedule if it changed.
+fn loop_schedules(first_run: Option<DateTime<Utc>>) {
+    let mut watcher = reload::Watcher::new();
+    let mut scheduler: Option<Scheduler<Utc>> = None;
+
     loop {
-        scheduler.run_pending();
+        metrics::tick();
+
+        let reloaded = watcher.poll();
+
+        match &mut scheduler {
+            Some(s) => {
+                if reloaded.is_some_and(|r| r.schedule_changed) {
+                    info!("The schedule changed, the next run starts counting from now.");
+                    *s = setup_tasks();
+                }
+                s.run_pending();
+            }
+            None => {
+                if first_run.is_none_or(|t| Utc::now() >= t) {
+                    run_tasks();
+                    scheduler = Some(setup_tasks());
+                }
+            }
+        }
+
         // print!(".");
         // let _ = stdout().flush();
         sleep(Duration::from_millis(500));
     }
 }
 
-fn setup_tasks(config: AppConfig) -> Scheduler<Utc> {
+/// Schedules the tasks according to the current config.
+fn setup_tasks() -> Scheduler<Utc> {
     let mut scheduler = Scheduler::with_tz(Utc);
 
+    let config = match reload::current() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Couldn't set up the schedule, {}", e);
+            return scheduler;
+        }
+    };
+
     scheduler
         .every(config.days_between_interval())
         .run(run_tasks);
+    metrics::set_next_run(Utc::now() + config.days_between_days());
+
+    if config.commands.unwrap_or(false) && config.target_on_github() {
+        scheduler
+            .every(config.command_poll_interval())
+            .run(run_commands);
+    }
 
     return scheduler;
 }
 
+/// Mirrors once for `days_between: 0`, returning the error that stopped the run if one did.
+async fn run_once(config: &AppConfig) -> Result<(), Error> {
+    let (octocrab, bot_info) = connect(config).await?;
+
+    let mirror_list = MirrorList::default();
+    let mut report = mirror_prs(&octocrab, config, &bot_info, &mirror_list).await; //? Completely circumvents the scheduling and file writing all together.
+    notify::run_finished(config, &report).await;
+    if config.reconcile.unwrap_or(false) && config.target_on_github() {
+        reconcile::reconcile(&octocrab, config, &bot_info, &mirror_list).await;
+    }
+
+    return match report.take_fatal() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    };
+}
+
 fn run_tasks() {
-    let config = generate_config();
+    let _running = metrics::task_started();
 
-    let octocrab = octocrab::OctocrabBuilder::new()
-        .user_access_token(config.org_token.clone())
-        .build()
-        .expect("Octocrab failed to build");
+    // A bad run is only logged, the next scheduled one tries again.
+    let config = match reload::current() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Skipping this run, {}", e);
+            return;
+        }
+    };
+
+    metrics::set_next_run(Utc::now() + config.days_between_days());
+
+    let (octocrab, bot_info) = match block_on(connect(&config)) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Skipping this run, {}", e);
+            return;
+        }
+    };
+
+    info!("Running scheduled tasks at {}.", Local::now().to_rfc2822());
+
+    let mirror_list = MirrorList::default();
+    let report = block_on(mirror_prs(&octocrab, &config, &bot_info, &mirror_list));
+    block_on(notify::run_finished(&config, &report));
+
+    if config.reconcile.unwrap_or(false) && config.target_on_github() {
+        block_on(reconcile::reconcile(&octocrab, &config, &bot_info, &mirror_list));
+    }
+
+    // Only move the cutoff forward once everything up to it was looked at.
+    if !report.succeeded() {
+        warn!("The run stopped early, PRs since {} will be looked at again next run.", config.cursor());
+        return;
+    }
+
+    finalize(config, &report);
+}
+
+fn run_commands() {
+    let _running = metrics::task_started();
+
+    let mut config = match reload::current() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Skipping command check, {}", e);
+            return;
+        }
+    };
 
-    let bot_info = block_on(get_bot_info(&config));
+    let (octocrab, bot_info) = match block_on(connect(&config)) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Skipping command check, {}", e);
+            return;
+        }
+    };
+
+    block_on(commands::handle_commands(&octocrab, &mut config, &bot_info));
+
+    if let Err(e) = config.state.save(&config) {
+        error!("{}", e);
+    }
+}
+
+/// Builds the GitHub client and looks up who the bot is.
+async fn connect(config: &AppConfig) -> Result<(Octocrab, Author), Error> {
+    let octocrab = forge::github_client(&config.into_repo, &config.org_token)?;
+
+    let bot_info = get_bot_info(config).await?;
+
+    return Ok((octocrab, bot_info));
+}
+
+async fn mirror_prs(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, mirror_list: &MirrorList) -> RunReport {
+    let mut report = RunReport::new(config);
+    mirror_prs_reported(octocrab, config, bot_info, mirror_list, &mut report).await;
 
-    println!("Running scheduled tasks at {}.", Local::now().to_rfc2822());
+    if report.succeeded() {
+        metrics::set_last_success(Utc::now());
+    }
+    if config.target_on_github() {
+        match octocrab.ratelimit().get().await {
+            Ok(r) => metrics::set_rate_limit_remaining(r.resources.core.remaining as u64),
+            Err(e) => debug!("Failed to get the remaining rate limit: {}", e),
+        }
+    }
 
-    block_on(mirror_prs(&octocrab, &config, &bot_info));
+    report.write(config);
 
-    finalize(config);
+    return report;
 }
 
-async fn mirror_prs(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author) {
-    println!("Mirroring all merged PRs since {} from {}/{}/{} to {}/{}/{}.",
-        config.date_from_with_time(),
+async fn mirror_prs_reported(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, mirror_list: &MirrorList, report: &mut RunReport) {
+    info!("Mirroring all merged PRs since {} from {}/{}/{} to {}/{}/{}.",
+        config.cursor(),
         config.clone_repo.owner, config.clone_repo.name, config.clone_repo.branch,
         config.into_repo.owner, config.into_repo.name, config.into_repo.branch);
 
-    let mut all_prs = get_all_prs(&octocrab, &config).await;
+    let gather_start = Instant::now();
+    let all_prs = get_all_prs(&octocrab, &config).await;
+    report.set_gather_time(gather_start);
+
+    let mut all_prs = match all_prs {
+        Ok(p) => p,
+        Err(e) => {
+            report.error("Failed to gather upstream PRs", e);
+            return;
+        }
+    };
+    report.consider(&all_prs);
 
     if all_prs.is_empty() {
-        println!("No PRs found at all!");
+        info!("No PRs found at all!");
         return;
     }
 
-    println!("Found {} PRs starting at {} and ending at {}.",
+    info!("Found {} PRs starting at {} and ending at {}.",
         &all_prs.len(),
         &all_prs.first().unwrap().number,
         &all_prs.last().unwrap().number);
 
-    let date_time_cutoff: DateTime<Utc> = config.date_from_with_time().and_utc();
-
-    let debug = config.debug.unwrap_or(false);
+    let date_time_cutoff: DateTime<Utc> = config.cursor();
 
     // I know the following lines are gross.
-    // Debug info :)
-    if debug { println!("\nChecking for unmerged PRs."); }
-    all_prs.retain(|pr| { if debug && !pr.merged_at.is_some() { print!("Ignoring unmerged PR #{}, ", pr.number); } return pr.merged_at.is_some(); });
-    if debug { println!("\n\nChecking for cutoff date {}", date_time_cutoff); }
-    all_prs.retain(|pr| { if debug && pr.merged_at.unwrap() < date_time_cutoff { print!("Ignoring PR #{} merged before cutoff at {}, ", pr.number, pr.merged_at.unwrap()) } return pr.merged_at.unwrap() >= date_time_cutoff; });
-    if debug { println!("\n\nChecking for ignored users: {:?}", config.ignored_users); }
-    all_prs.retain(|pr| pr.user.to_owned().is_some_and(|user| { if config.ignored_users.contains(&user.login) { if debug { print!("Ignoring PR #{} made by ignored user {}, ", pr.number, &user.login) } return false } return true })); // This will also ignore any prs that don't have users I guess??
-    if debug { println!("\n\nChecking for ignored labels: {:?}", config.ignored_labels); }
-    all_prs.retain(|pr| pr.labels.to_owned().is_some_and(|labels| { if labels.iter().any(|label| config.ignored_labels.contains(&label.name)) { if debug { print!("Ignoring PR #{} with ignored label, ", pr.number) } return false } return true }));
-    if debug { println!("\n\n"); }
+    debug!("Checking for unmerged PRs.");
+    all_prs.retain(|pr| { if !pr.merged_at.is_some() { debug!("Ignoring unmerged PR #{}", pr.number); if pr.closed_at.is_some_and(|t| t < date_time_cutoff) { report.filter_old(pr.number, "unmerged") } else { report.filter(pr.number, "unmerged") } } return pr.merged_at.is_some(); });
+    debug!("Checking for cutoff date {}", date_time_cutoff);
+    // Commits of a plain git repo are already only those after the last run's head, whatever their dates.
+    all_prs.retain(|pr| { if config.last_commit().is_none() && pr.merged_at.unwrap() < date_time_cutoff { debug!("Ignoring PR #{} merged before cutoff at {}", pr.number, pr.merged_at.unwrap()); report.filter_old(pr.number, "merged before cutoff"); return false } return true });
+    debug!("Checking for ignored users: {:?}", config.ignored_users);
+    all_prs.retain(|pr| pr.user.to_owned().is_some_and(|user| { if config.ignored_users.contains(&user.login) { debug!("Ignoring PR #{} made by ignored user {}", pr.number, &user.login); report.filter(pr.number, "ignored user"); return false } return true })); // This will also ignore any prs that don't have users I guess??
+    debug!("Checking for ignored labels: {:?}", config.ignored_labels);
+    all_prs.retain(|pr| pr.labels.to_owned().is_some_and(|labels| { if labels.iter().any(|label| config.ignored_labels.contains(&label.name)) { debug!("Ignoring PR #{} with ignored label", pr.number); report.filter(pr.number, "ignored label"); return false } return true }));
+    debug!("Checking for skipped PRs: {:?} {:?}", config.skipped_prs, config.state.skipped_prs);
+    all_prs.retain(|pr| { if config.is_skipped(pr.number) { debug!("Ignoring skipped PR #{}", pr.number); report.filter(pr.number, "skipped"); return false } return true });
     all_prs.sort_unstable_by_key(|pr| pr.merged_at);
 
     if all_prs.is_empty() {
-        println!("No valid PRs found.");
+        info!("No valid PRs found.");
         return;
     }
 
-    println!("Filtered down to {} PRs starting at {} and ending at {}.",
+    info!("Filtered down to {} PRs starting at {} and ending at {}.",
         all_prs.len(),
         all_prs.first().unwrap().number,
         all_prs.last().unwrap().number);
 
+    let before_reverts: Vec<u64> = all_prs.iter().map(|pr| pr.number).collect();
+    let reverted_mirrors = reverts::handle_reverts(octocrab, config, bot_info, &mut all_prs, mirror_list).await;
+    for number in before_reverts.into_iter().filter(|n| !all_prs.iter().any(|pr| pr.number == *n)) {
+        report.filter(number, "reverts an open mirror, which was closed instead");
+    }
+
+    // A crash can leave the local repo mid cherry-pick or otherwise broken, which would fail every PR.
+    match git_utils::repair_repo(config) {
+        Ok(repairs) => {
+            for repair in &repairs {
+                warn!("Repaired the local repo: {}", repair);
+            }
+            report.set_repairs(repairs);
+        }
+        Err(e) => error!("Failed to check over the local repository: {}", e),
+    }
+
     let repo = match git_utils::ensure_repo(&config, &bot_info) {
         Ok(r) => r,
         Err(e) => {
-            eprintln!("Failed to get or create local repository: {}", e);
+            error!("Failed to get or create local repository: {}", e);
+            report.error("Failed to get or create local repository", e);
+            return;
+        }
+    };
+
+    // Mirror PRs are opened from the fork, so its base branch shouldn't fall behind the target's.
+    if config.pushes_to_fork() {
+        if let Err(e) = git_utils::sync_fork(&repo, config, bot_info) {
+            warn!("Failed to sync the bot's fork with {}/{}/{}: {}", config.into_repo.owner, config.into_repo.name, config.into_repo.branch, e);
+        }
+    }
+    if config.cleanup_branches.unwrap_or(true) && config.target_on_github() {
+        mirrors::cleanup_branches(octocrab, config, bot_info, &repo, mirror_list).await;
+    }
+
+    // Everything the run cherry-picks is fetched and checked for at once, rather than once per PR.
+    let fetch_start = Instant::now();
+    let needed: Vec<(u64, &str)> = all_prs.iter().filter_map(|pr| Some((pr.number, pr.merge_commit_sha.as_deref()?))).collect();
+    let fetched = git_utils::fetch_for_run(&repo, config, &needed).stage(Stage::Fetch);
+    report.set_fetch_time(fetch_start);
+    let missing = match fetched {
+        Ok(m) => m,
+        Err(e) => {
+            error!("Failed to fetch upstream commits: {}", e);
+            report.error("Failed to fetch upstream commits", e);
             return;
         }
     };
+    info!("Fetched {} upstream commits in {:.2}s.", needed.len() - missing.len(), fetch_start.elapsed().as_secs_f64());
+    for number in &missing {
+        warn!("The merge commit of PR #{} couldn't be fetched from upstream, it won't be mirrored.", number);
+    }
+
+    // The last mirror we made, used as the base of the next one when stacking.
+    let mut previous_mirror: Option<MirrorBase> = None;
 
     for merged_pr in all_prs.iter() {
-        println!("Cherry-picking and pushing PR #{}.", merged_pr.number);
-        match cherry_pick_and_push_pr(&repo, &octocrab, merged_pr.clone(), &config, &bot_info) {
-            Ok(_) => {
-                println!("Cherry-picked and pushed PR #{}.", merged_pr.number);
+        let reset = async {
+            info!("Cherry-picking and pushing PR #{}.", merged_pr.number);
+            let reverts = reverted_mirrors.get(&merged_pr.number).copied();
+            let branch_name = mirror_branch_name(config, merged_pr);
+            let mut timings = PhaseTimings::default();
+
+            let mut attempt = 0;
+            let result = loop {
+                // Its commit was already looked for before the loop, there's no point cherry-picking.
+                if missing.contains(&merged_pr.number) {
+                    break Err(missing_commit(merged_pr));
+                }
+
+                let result = cherry_pick_and_push_pr(&repo, &octocrab, merged_pr.clone(), &config, &bot_info, previous_mirror.as_ref(), reverts, &mut timings)
+                    .map_err(|e| e.for_pr(merged_pr.number, merged_pr.merge_commit_sha.as_deref()));
+
+                match result {
+                    Err(e) if e.class() == Class::Transient => {
+                        // The PR may have been opened even though the forge didn't answer in time, retrying would open it twice.
+                        if e.stage() == Some(Stage::PrCreate) {
+                            if let Some(existing) = existing_mirror(&repo, octocrab, config, bot_info, &branch_name).await {
+                                break Ok(existing);
+                            }
+                        }
+                        if attempt >= config.transient_retries() {
+                            break Err(e);
+                        }
+
+                        attempt += 1;
+                        let wait = Duration::from_secs(10 * 2u64.pow(attempt));
+                        warn!("Mirroring PR #{} failed with a transient error, retrying in {}s ({}/{}): {}", merged_pr.number, wait.as_secs(), attempt, config.transient_retries(), e);
+                        if let Err(e) = git_utils::reset_repo(&repo, config) {
+                            break Err(e);
+                        }
+                        tokio::time::sleep(wait).await;
+                    }
+                    result => break result,
+                }
+            };
+
+            let (decision, pr_url, issue_url, error) = match result {
+                Ok(mirror) => {
+                    info!("Cherry-picked and pushed PR #{}.", merged_pr.number);
+                    metrics::record_mirrored();
+                    let pr_url = mirror.pr_url.clone();
+                    previous_mirror = Some(mirror);
+                    (Decision::Mirrored, pr_url, None, None)
+                }
+                Err(e) => {
+                    error!("Failed to cherry-pick and push PR #{} {}: {}", merged_pr.number, merged_pr.title.clone().unwrap_or_default(), e);
+                    info!("{}", e.guidance());
+                    let message = e.to_string();
+                    metrics::record_failed(&e);
+                    let issue_url = make_issue(&config, &octocrab, merged_pr.clone(), e).await; // Report if something goes wrong.
+                    (Decision::Failed, None, issue_url, Some(message))
+                }
+            };
+
+            if let Some(pr) = report.pr(merged_pr.number) {
+                pr.decision = decision;
+                pr.branch = Some(branch_name);
+                pr.pr_url = pr_url;
+                pr.issue_url = issue_url;
+                pr.timings = timings;
+                pr.error = error;
             }
-            Err(e) => {
-                eprintln!("Failed to cherry-pick and push PR #{} {}: {}", merged_pr.number, merged_pr.title.clone().unwrap_or_default(), e);
-                make_issue(&config, &octocrab, merged_pr.clone(), e).await; // Report if something goes wrong.
+
+            if let Err(e) = git_utils::reset_repo(&repo, &config) {
+                error!("Failed to reset repository after cherry-picking PR #{}.", merged_pr.number);
+                report.error(&format!("Failed to reset repository after cherry-picking PR #{}, stopping the run", merged_pr.number), e);
+                return false;
             }
+
+            return true;
         }
+        .instrument(pr_span(merged_pr))
+        .await;
 
-        if git_utils::reset_repo(&repo, &config).is_err() {
-            eprintln!("Failed to reset repository after cherry-picking PR #{}.", merged_pr.number);
+        if !reset {
             return;
         }
-
-        println!(""); // New line to seperate them.
     }
 }
 
-fn cherry_pick_and_push_pr(repo: &Repository, octocrab: &Octocrab, merged_pr: PullRequest, config: &AppConfig, bot_info: &Author) -> Result<(), Error> {
+/// The mirror already opened from `branch`, for when creating its PR failed but may have gone through anyway.
+async fn existing_mirror(repo: &Repository, octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, branch: &str) -> Option<MirrorBase> {
+    let target = config.target(octocrab).ok()?;
+    let pr = target
+        .find_open_pr(&config.into_repo, &mirror_head(config, bot_info, branch))
+        .await
+        .inspect_err(|e| warn!("Failed to check whether the mirror PR from {} was opened anyway: {}", branch, e))
+        .ok()??;
+
+    let commit = repo.refname_to_id(&format!("refs/heads/{}", branch)).ok()?;
+    info!("Creating the PR from {} failed, but it was opened anyway as #{}.", branch, pr.number);
+
+    return Some(MirrorBase {
+        branch: branch.to_string(),
+        commit,
+        pr_number: Some(pr.number),
+        pr_url: pr.html_url.map(|u| u.to_string()),
+    });
+}
+
+/// The head a mirror PR from `branch` is opened from.
+fn mirror_head(config: &AppConfig, bot_info: &Author, branch: &str) -> String {
+    return match config.pushes_to_fork() {
+        true => format!("{}:{}", &bot_info.login, branch),
+        false => branch.to_string(),
+    };
+}
+
+/// The error for a PR whose merge commit couldn't be fetched before the run started.
+fn missing_commit(pr: &PullRequest) -> Error {
+    let sha = pr.merge_commit_sha.as_deref();
+    return Error::General(format!("Merge commit {} couldn't be fetched from upstream", sha.unwrap_or_default()))
+        .at(Stage::Fetch)
+        .for_pr(pr.number, sha);
+}
+
+/// A span for logs about mirroring a single upstream PR.
+fn pr_span(pr: &PullRequest) -> Span {
+    return info_span!("pr", number = pr.number, sha = pr.merge_commit_sha.as_deref().unwrap_or_default());
+}
+
+/// The name of the branch a mirror of the given upstream PR is pushed to.
+fn mirror_branch_name(config: &AppConfig, pr: &PullRequest) -> String {
+    return format!("{}{}_{}_{}_{}",
+        config.branch_prefix(),
+        &config.clone_repo.owner,
+        &config.clone_repo.name,
+        pr.number,
+        Utc::now().date_naive());
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cherry_pick_and_push_pr(repo: &Repository, octocrab: &Octocrab, merged_pr: PullRequest, config: &AppConfig, bot_info: &Author, previous: Option<&MirrorBase>, reverts: Option<u64>, timings: &mut PhaseTimings) -> Result<MirrorBase, Error> {
     let sha = match merged_pr.merge_commit_sha.to_owned() {
         Some(s) => s,
         None => {
-            eprintln!("PR #{} has no merge commit SHA.", merged_pr.number);
+            error!("PR #{} has no merge commit SHA.", merged_pr.number);
             return Err(Error::General("Octocrab returned PR with no sha".to_string()));
         }
     };
 
-    let branch_name = format!("{}_{}_{}_{}",
-        &config.clone_repo.owner,
-        &config.clone_repo.name,
-        merged_pr.number,
-        Utc::now().date_naive());
+    let branch_name = mirror_branch_name(config, &merged_pr);
+
+    check_single_commit(octocrab, config, &merged_pr, &sha)?;
+
+    let stack_mode = config.stack_mode.unwrap_or_default();
+    let mut base = match stack_mode {
+        StackMode::Always => previous,
+        _ => None,
+    };
+
+    let commit = match create_mirror_commit(repo, config, bot_info, &branch_name, &sha, base, timings) {
+        Ok(c) => c,
+        Err(e) if stack_mode == StackMode::OnConflict && previous.is_some() => {
+            let previous = previous.unwrap();
+            info!("Cherry-picking onto {} failed ({}), retrying on top of mirror branch {}.", config.into_repo.branch, e, previous.branch);
+            git_utils::reset_repo(repo, config)?;
+            base = Some(previous);
+            create_mirror_commit(repo, config, bot_info, &branch_name, &sha, base, timings)?
+        }
+        Err(e) => return Err(e),
+    };
+
+    info!("Pushing to remote branch {}.", branch_name);
+    timings.time(Phase::Push, || git_utils::push_to_remote(&repo, &config, &bot_info)).stage(Stage::Push)?;
+
+    info!("Making pull request for {}.", branch_name);
+    let links = MirrorLinks {
+        depends_on: base.and_then(|b| b.pr_number),
+        reverts,
+    };
+    let pr = timings.time(Phase::PrCreation, || block_on(make_pull_request(&config, &octocrab, &bot_info, merged_pr, Some(sha), &branch_name, links))).stage(Stage::PrCreate)?;
+
+    return Ok(MirrorBase {
+        branch: branch_name,
+        commit,
+        pr_number: pr.as_ref().map(|p| p.number),
+        pr_url: pr.and_then(|p| p.html_url).map(|u| u.to_string()),
+    });
+}
+
+/// Fails a PR that was fast-forwarded as several commits. Without a merge or squash commit to pick, like GitLab merge requests
+/// merged that way, the PR's head is all there is to go on, and picking it alone would leave the rest of its commits behind.
+fn check_single_commit(octocrab: &Octocrab, config: &AppConfig, pr: &PullRequest, sha: &str) -> Result<(), Error> {
+    if config.clone_repo.forge != ForgeKind::Gitlab || pr.head.sha != sha {
+        return Ok(());
+    }
+
+    // Listed merge requests don't say how many commits they have.
+    let commits = match pr.commits {
+        Some(c) => c,
+        None => block_on(config.upstream(octocrab)?.get_pr(&config.clone_repo, pr.number)).stage(Stage::Fetch)?.commits.unwrap_or(1),
+    };
+    if commits <= 1 {
+        return Ok(());
+    }
 
-    println!("Creating branch {}.", branch_name);
-    git_utils::create_branch(&repo, &branch_name)?;
+    return Err(Error::General(format!("PR #{} was merged as {} commits without a merge or squash commit, so it can't be mirrored as one. \
+        Mirror its commits by hand, or merge it upstream with a merge or squash commit", pr.number, commits))).stage(Stage::CherryPick);
+}
 
-    println!("Cherry-picking commit {}.", &sha);
-    git_utils::cherry_pick_commit(&repo, &config, &bot_info, &sha)?;
+/// Creates the mirror branch, on top of `base` if given, and cherry-picks the merge commit onto it.
+fn create_mirror_commit(repo: &Repository, config: &AppConfig, bot_info: &Author, branch_name: &str, sha: &str, base: Option<&MirrorBase>, timings: &mut PhaseTimings) -> Result<Oid, Error> {
+    info!("Creating branch {}.", branch_name);
+    git_utils::create_branch(repo, branch_name, base.map(|b| b.commit)).stage(Stage::Branch)?;
 
-    println!("Pushing to remote branch {}.", branch_name);
-    git_utils::push_to_remote(&repo, &config, &bot_info)?;
+    // Runs fetch everything up front, this is for mirrors made from commands and reconciling.
+    if !git_utils::has_commit(repo, sha) {
+        timings.time(Phase::Fetch, || git_utils::fetch_upstream(repo, config, &[sha])).stage(Stage::Fetch)?;
+    }
 
-    println!("Making pull request for {}.", branch_name);
-    block_on(make_pull_request(&config, &octocrab, &bot_info, merged_pr, Some(sha), &branch_name))?;
+    info!("Cherry-picking commit {}.", sha);
+    timings.time(Phase::CherryPick, || git_utils::cherry_pick_commit(repo, config, bot_info, sha)).stage(Stage::CherryPick)?;
 
-    return Ok(());
+    return Ok(repo.head()?.peel_to_commit()?.id());
 }
 
-async fn make_pull_request(config: &AppConfig, octocrab: &Octocrab, bot_info: &Author, original_pr: PullRequest, merge_sha: Option<String>, branch: &str) -> Result<(), Error> {
+/// Opens the mirror PR, returning it if one was made.
+async fn make_pull_request(config: &AppConfig, octocrab: &Octocrab, bot_info: &Author, original_pr: PullRequest, merge_sha: Option<String>, branch: &str, links: MirrorLinks) -> Result<Option<PullRequest>, Error> {
     let merge_commit = match &merge_sha {
-        Some(s) => {
-            let commit = octocrab
-                .commits(&config.clone_repo.owner, &config.clone_repo.name)
-                .get(s)
-                .await;
-
-            match commit {
-                Ok(c) => Some(c),
-                Err(_) => None,
-            }
-        }
+        Some(s) => get_upstream_commit(octocrab, config, s).await,
         None => None,
     };
 
-    let filled_template = pr_template::PrTemplate::new(&original_pr, merge_commit);
-    let head = format!("{}:{}", &bot_info.login, branch);
-    let base = config.into_repo.branch.clone();
+    let assignees = assignees::pick_assignees(octocrab, config, merge_commit.as_ref()).await;
+    let mut labels = config.pr_labels.clone();
+    labels.extend(labels::translate_labels(octocrab, config, &original_pr, merge_commit.as_ref()).await);
+
+    let mut filled_template = pr_template::PrTemplate::new(&original_pr, merge_commit);
+    if let Some(number) = links.depends_on {
+        filled_template.set_depends_on(number);
+    }
+    if let Some(number) = links.reverts {
+        filled_template.set_reverts(number);
+    }
+    let target = config.target(octocrab)?;
+    let title = filled_template.get_title();
+    let body = filled_template.get_body();
+    let head = mirror_head(config, bot_info, branch);
+    let new_pr = NewPr {
+        title: &title,
+        head: &head,
+        base: &config.into_repo.branch,
+        body: &body,
+        draft: config.pr_settings.draft,
+    };
 
     if NO_NET_ACTIVITY {
         if PRINT_PRS {
-            println!("\n-------------\n{}\n{}\n-------------\n", filled_template.get_title(), filled_template.get_body());
+            info!("\n-------------\n{}\n{}\n-------------", title, body);
         }
     
-        return Ok(());
+        return Ok(None);
     }
 
-    let pr_attempt = match timeout(Duration::from_secs(10), send_pull_request(octocrab, config, &filled_template.get_title(), &head, &base, &filled_template.get_body())).await {
+    let pr_attempt = match timeout(Duration::from_secs(10), target.create_pr(&config.into_repo, &new_pr)).await {
         Ok(p) => {
-            p.inspect_err(|e| {
-                eprintln!("Failed to create pull request for {}: {}\nSha: {}", original_pr.number, e, merge_sha.unwrap_or_default());
-                eprintln!("This is probably a permissions issue.");
-            })
+            p.inspect_err(|e| error!("Failed to create pull request for {}: {}\nSha: {}", original_pr.number, e, merge_sha.unwrap_or_default()))
         },
         Err(_) => {
-            eprintln!("Timed out creating pull request for PR #{}.\nBot will sit idle for one minute before attempting again.", original_pr.number);
-            sleep(Duration::from_secs(60));
-            
-            match timeout(Duration::from_secs(10), send_pull_request(octocrab, config, &filled_template.get_title(), &head, &base, &filled_template.get_body())).await {
-                Ok(p) => p.inspect_err(|e| {
-                    eprintln!("Failed to create pull request for {}: {}\nSha: {}", original_pr.number, e, merge_sha.unwrap_or_default());
-                    eprintln!("This is probably a permissions issue.");
-                }),
-                Err(_) => {
-                    eprintln!("Failed to create pull request for PR #{} after two attempts. Giving up.", original_pr.number);
-                    return Err(Error::General("Failed to create pull request after two attempts.".to_string()));
+            error!("Timed out creating pull request for PR #{}.\nBot will sit idle for one minute before attempting again.", original_pr.number);
+            tokio::time::sleep(Duration::from_secs(60)).await;
+
+            // It may have been opened even though GitHub didn't answer in time.
+            let existing = target.find_open_pr(&config.into_repo, &head).await.ok().flatten();
+            match existing {
+                Some(pr) => Ok(pr),
+                None => match timeout(Duration::from_secs(10), target.create_pr(&config.into_repo, &new_pr)).await {
+                    Ok(p) => p.inspect_err(|e| error!("Failed to create pull request for {}: {}\nSha: {}", original_pr.number, e, merge_sha.unwrap_or_default())),
+                    Err(_) => {
+                        error!("Failed to create pull request for PR #{} after two attempts. Giving up.", original_pr.number);
+                        return Err(Error::Timeout("Failed to create pull request after two attempts.".to_string()));
+                    }
                 }
             }
         }
     };
 
-    if pr_attempt.is_ok() {
-        let pr = pr_attempt.unwrap();
+    let pr = pr_attempt?;
+    assignees::pass_turn(config);
 
-        let _ = octocrab
-            .issues(&config.into_repo.owner, &config.into_repo.name)
-            .add_labels(pr.number, &config.pr_labels)
-            .await
-            .inspect_err(|e| eprintln!("Failed to add labels to PR #{}: {}", pr.number, e));
-    };
+    let labeled = target
+        .add_labels(&config.into_repo, pr.number, &labels)
+        .await
+        .stage(Stage::Labeling);
+    if let Err(e) = labeled {
+        warn!("Failed to add labels to PR #{}: {}", pr.number, e);
+        warn!("{}", e.guidance());
+    }
+
+    if config.target_on_github() {
+        apply_pr_settings(octocrab, config, pr.number, &assignees).await;
+    }
 
-    return Ok(());
+    return Ok(Some(pr));
 }
 
-async fn send_pull_request(octocrab: &Octocrab, config: &AppConfig, title: &String, head: &String, base: &String, body: &String) -> Result<PullRequest, OctoError> {
-    return octocrab
-        .pulls(&config.into_repo.owner, &config.into_repo.name)
-        .create(title, head, base)
-        .body(body)
-        .draft(true)
-        .maintainer_can_modify(true)
-        .send()
-        .await;
+/// Applies the assignees, reviewers and milestone from `pr_settings` to a PR the bot made.
+async fn apply_pr_settings(octocrab: &Octocrab, config: &AppConfig, number: u64, assignees: &[String]) {
+    let settings = &config.pr_settings;
+    let issues = octocrab.issues(&config.into_repo.owner, &config.into_repo.name);
+
+    if !assignees.is_empty() {
+        let assignees: Vec<&str> = assignees.iter().map(|a| a.as_str()).collect();
+        let _ = issues
+            .add_assignees(number, &assignees)
+            .await
+            .inspect_err(|e| warn!("Failed to add assignees to PR #{}: {}", number, e));
+    }
+
+    if !settings.reviewers.is_empty() || !settings.team_reviewers.is_empty() {
+        let route = format!("/repos/{}/{}/pulls/{}/requested_reviewers", config.into_repo.owner, config.into_repo.name, number);
+        let reviewers = serde_json::json!({
+            "reviewers": settings.reviewers,
+            "team_reviewers": settings.team_reviewers,
+        });
+
+        let _ = octocrab
+            .post::<_, serde_json::Value>(route, Some(&reviewers))
+            .await
+            .inspect_err(|e| warn!("Failed to request reviewers for PR #{}: {}", number, e));
+    }
+
+    if let Some(milestone) = settings.milestone {
+        let _ = issues
+            .update(number)
+            .milestone(milestone)
+            .send()
+            .await
+            .inspect_err(|e| warn!("Failed to set milestone of PR #{}: {}", number, e));
+    }
 }
 
-async fn make_issue(config: &AppConfig, octocrab: &Octocrab, pr: PullRequest, error: Error) {
+/// Opens an issue about a PR that failed to mirror, returning its URL if one was made.
+async fn make_issue(config: &AppConfig, octocrab: &Octocrab, pr: PullRequest, error: Error) -> Option<String> {
     let merge_commit = match pr.merge_commit_sha {
-        Some(ref s) => {
-            let commit = octocrab
-                .commits(&config.clone_repo.owner, &config.clone_repo.name)
-                .get(s)
-                .await;
-
-            match commit {
-                Ok(c) => Some(c),
-                Err(_) => None,
-            }
-        }
+        Some(ref s) => get_upstream_commit(octocrab, config, s).await,
         None => None,
     };
 
-    let title = format!("Failed to cherry-pick PR #{}: {}", pr.number, pr.title.clone().unwrap_or_default());
-    let body = format!("## Failed to cherry-pick PR: {}\nPR body below\n\n{}", error, pr_template::PrTemplate::new(&pr, merge_commit).get_body());
+    let assignees = assignees::pick_assignees(octocrab, config, merge_commit.as_ref()).await;
+    let template = pr_template::PrTemplate::new(&pr, merge_commit);
+    let title = template.get_failure_title();
+    let stage = error.stage().map(|s| s.to_string()).unwrap_or("unknown".to_string());
+    let body = format!("## Failed to cherry-pick PR: {}\n**Stage:** {}, **Kind:** {}\n\n> [!TIP]\n> {}\n\nPR body below\n\n{}",
+        error, stage, error.class(), error.guidance(), template.get_body());
+
+    let mut issue_url = None;
 
     if !NO_NET_ACTIVITY {
-        let issue_handler = octocrab
-            .issues(&config.into_repo.owner, &config.into_repo.name)
-            .create(&title)
-            .body(&body)
-            // .assignees(assignees) //TODO: Automatic assignees?
-            .labels(Some(config.issue_labels.to_owned()))
-            .send()
-            .await;
+        let issue = NewIssue {
+            title: &title,
+            body: &body,
+            assignees: &assignees,
+            milestone: config.pr_settings.milestone,
+            labels: &config.issue_labels,
+        };
+        let issue_handler = match config.target(octocrab) {
+            Ok(target) => target.create_issue(&config.into_repo, &issue).await,
+            Err(e) => Err(e),
+        };
 
-        if issue_handler.is_err() {
-            eprintln!("Failed to create issue for missed PR #{}: {}", pr.number, issue_handler.err().unwrap());
-            eprintln!("This is probably a permissions issue.");
+        match issue_handler {
+            Ok(url) => {
+                metrics::record_issue();
+                assignees::pass_turn(config);
+                issue_url = Some(url);
+            }
+            Err(e) => {
+                error!("Failed to create issue for missed PR #{}: {}", pr.number, e);
+                warn!("{}", e.guidance());
+            }
         }
     }
 
     if PRINT_PRS {
-        println!("-------------\n{}\n{}\n-------------", title, &body);
+        info!("\n-------------\n{}\n{}\n-------------", title, &body);
     }
+
+    notify::mirror_failed(config, &pr, &error, issue_url.as_deref()).await;
+
+    return issue_url;
 }
 
-async fn get_all_prs(octocrab: &Octocrab, config: &AppConfig) -> Vec<PullRequest> {
+/// Looks up a commit in `clone_repo`, for the files and stats it changed.
+async fn get_upstream_commit(octocrab: &Octocrab, config: &AppConfig, sha: &str) -> Option<RepoCommit> {
+    let upstream = config.upstream(octocrab).ok()?;
+
+    return upstream
+        .get_commit(&config.clone_repo, sha)
+        .await
+        .inspect_err(|e| debug!("Failed to get upstream commit {}: {}", sha, e))
+        .ok();
+}
+
+async fn get_all_prs(octocrab: &Octocrab, config: &AppConfig) -> Result<Vec<PullRequest>, Error> {
+    let upstream = config.upstream(octocrab)?;
     let forced_prs: Option<Vec<u64>> = match !CHERRY_PICK_ONLY.is_empty() {
         true => Some(CHERRY_PICK_ONLY.into()),
         false => match !config.prs_to_pull.is_empty() {
@@ -363,14 +872,10 @@ async fn get_all_prs(octocrab: &Octocrab, config: &AppConfig) -> Vec<PullRequest
     if forced_prs.is_some() {
         let mut prs = Vec::new();
         for num in CHERRY_PICK_ONLY.iter() {
-            let pr = match octocrab
-                .pulls(&config.clone_repo.owner, &config.clone_repo.name)
-                .get(*num)
-                .await
-            {
+            let pr = match upstream.get_pr(&config.clone_repo, *num).await {
                 Ok(p) => p,
                 Err(err) => {
-                    eprintln!("Failed to get PR by number {}: {}", num, err);
+                    error!("Failed to get PR by number {}: {}", num, err);
                     continue;
                 }
             };
@@ -378,42 +883,32 @@ async fn get_all_prs(octocrab: &Octocrab, config: &AppConfig) -> Vec<PullRequest
             prs.push(pr);
         }
 
-        return prs;
+        return Ok(prs);
     }
 
     // Returns the first page of all prs.
-    let mut page = match octocrab
-        .pulls(&config.clone_repo.owner, &config.clone_repo.name)
-        .list()
-        .sort(Sort::Created)
-        .direction(Direction::Descending)
-        .base(&config.clone_repo.branch)
-        .state(params::State::Closed)
-        .per_page(100)
-        .send()
-        .await
-    {
+    let page = match upstream.list_closed_prs(&config.clone_repo, 1).await {
         Ok(p) => p,
         Err(err) => {
-            eprintln!("Failed to get first page of PRs for {}/{}: {}",
+            error!("Failed to get first page of PRs for {}/{}: {}",
                 config.clone_repo.owner, config.clone_repo.name, err);
-            return Vec::new();
+            return Err(err);
         }
     };
 
     // Start our collection.
-    let mut all_prs = page.take_items();
+    let mut all_prs = page.prs;
 
     // Getting all PRs takes a very long time, so we check if we should skip it.
     if FIRST_100_ONLY {
-        println!("Retrieving only the first 100 PRs.");
-        return all_prs;
+        info!("Retrieving only the first 100 PRs.");
+        return Ok(all_prs);
     }
 
-    println!("Attempting to gather all PR data- this may take a while...");
+    info!("Attempting to gather all PR data- this may take a while...");
 
     // Determine how many pages there are, and how many times to call async.
-    let total_pages = page.number_of_pages().unwrap_or(1);
+    let total_pages = page.pages.unwrap_or(1);
     let total_pages = if let Some(hard_cap) = &config.hard_cap { hard_cap.min(&total_pages) } else { &total_pages };
     let pages_per_thread = total_pages / &config.max_async.unwrap_or(2);
 
@@ -425,176 +920,116 @@ async fn get_all_prs(octocrab: &Octocrab, config: &AppConfig) -> Vec<PullRequest
         let end = i + pages_per_thread;
         i = end + 1;
 
-        futures.push(get_prs_from_page_to(octocrab, config, start, end));
-
-        let _ = std::io::stdout().flush();
+        futures.push(get_prs_from_page_to(upstream.as_ref(), config, start, end.min(*total_pages)));
     }
 
     // Gather all PRs.
-    all_prs.extend(futures::future::join_all(futures).await.into_iter().flatten());
+    // A missing page would leave its PRs behind the cursor for good, so any failure fails the whole gather.
+    for prs in futures::future::join_all(futures).await {
+        all_prs.extend(prs?);
+    }
 
     // Remove duplicates.
     all_prs.sort_unstable_by_key(|pr| pr.number);
     all_prs.dedup_by(|a, b| a.number == b.number);
 
-    println!("Done gathering all PRs!");
+    info!("Done gathering all PRs!");
 
-    return all_prs;
+    return Ok(all_prs);
 }
 
-async fn get_prs_from_page_to(octocrab: &Octocrab, config: &AppConfig, page_start: u32, page_end: u32) -> Vec<PullRequest> {
-    let mut page = octocrab
-        .pulls(&config.clone_repo.owner, &config.clone_repo.name)
-        .list()
-        .sort(Sort::Created)
-        .direction(Direction::Descending)
-        .base(&config.clone_repo.branch)
-        .state(params::State::Closed)
-        .per_page(100)
-        .page(page_start)
-        .send()
-        .await
-        .inspect_err(|e| eprintln!("Failed to get page #{} of PRs: {}", page_start, e))
-        .unwrap();
-
+async fn get_prs_from_page_to(upstream: &dyn Forge, config: &AppConfig, page_start: u32, page_end: u32) -> Result<Vec<PullRequest>, Error> {
     let mut collection = vec![];
-    collection.extend(page.take_items());
 
-    let mut i = page_start + 1;
-    while page.next.is_some() && i <= page_end {
-        page = match octocrab.get_page(&page.next.clone()).await {
-            Ok(p) => p.unwrap_or_else(|| panic!("Failed to get next page of PRs: No data returned.\nUnsure of how to continue.")),
+    for i in page_start..=page_end {
+        let page = match upstream.list_closed_prs(&config.clone_repo, i).await {
+            Ok(p) => p,
             Err(err) => {
-                eprintln!("Failed to get next page of PRs: {}\nAre you being rate limited?", err);
-                return collection;
+                error!("Failed to get page #{} of PRs: {}\nAre you being rate limited?", i, err);
+                return Err(err);
             }
         };
 
-        collection.extend(page.take_items());
-
-        print!("Done with page #{}, PR #{}...\r", i, collection.last().unwrap().number);
-        let _ = std::io::stdout().flush();
+        collection.extend(page.prs);
 
-        i += 1;
-        // i = page //TODO: This sucks :P
-        //     .prev
-        //     .unwrap()
-        //     .to_string()
-        //     .split("page=")
-        //     .last()
-        //     .unwrap()
-        //     .parse()
-        //     .unwrap();
+        debug!("Done with page #{}, PR #{}...", i, collection.last().map(|pr| pr.number).unwrap_or_default());
     }
 
-    return collection;
+    return Ok(collection);
 }
 
-fn finalize(mut config: AppConfig) {
-    // Set our date_from to the current date, so we only pick up new PRs next time we run.
-    config.date_from = Utc::now().date_naive();
-    // Set our exact time offset, to ensure we don't miss any PRs.
-    config.time_offset = Utc::now().time().into();
-
-    println!("Updating {}.", FILE_NAME);
+fn finalize(mut config: AppConfig, report: &RunReport) {
+    // Move the cursor to now, so we only pick up new PRs next time we run.
+    config.state.cursor = Some(Utc::now());
+    config.state.last_run = config.state.cursor;
+    if let Some(number) = report.last_pr() {
+        config.state.last_pr = Some(number);
+    }
+    if config.clone_repo.forge == ForgeKind::Git {
+        config.state.last_commit = forge::git::fetched_head(&config.source_path(), &config.clone_repo.branch);
+    }
 
-    // Write the new config to the file.
-    let yaml_contents = serde_yaml::to_string(&config).unwrap();
-    write_to_config(yaml_contents, Some(&config));
+    if let Err(e) = config.state.save(&config) {
+        error!("{}", e);
+    }
 }
 
-fn generate_config() -> AppConfig {
+fn generate_config() -> Result<AppConfig, Error> {
     // Create the file if it doesn't exist.
     if !Path::new(FILE_NAME).exists() {
         println!("Config file does not exist, attempting to create it at {}/{}.",
             std::env::current_dir()
-                .expect("Couldn't find current dir! Are we lacking permissions?")
-                .to_str()
+                .map(|d| d.to_string_lossy().into_owned())
                 .unwrap_or_default(),
             FILE_NAME);
-        write_to_config(YAML_TEMPLATE.to_string(), None);
-        panic!("Config file {} created. Please fill in the necessary information and run the program again.", FILE_NAME);
+        write_to_config(YAML_TEMPLATE.to_string())?;
+        return Err(Error::Config(format!("Config file {} created. Please fill in the necessary information and run the program again.", FILE_NAME)));
     }
 
-    let yaml_contents = fs::read_to_string(FILE_NAME).expect(&format!("Config file {} was confirmed to exist, but could not be read.\nAre we missing permissions?.", FILE_NAME));
-    return match serde_yaml::from_str(&yaml_contents) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Failed to parse config file: {}", e);
+    let mut config = read_config()?;
+    config.state = State::load(&config)?;
+    return Ok(config);
+}
 
-            //-- This timesout in case we're running in a non-interactive environment.
-            //--  (timesout in 120 seconds)
-            //TODO: This just straight up doesn't work right now, but it doesn't break anything so I left it in.
-            if block_on(timeout(Duration::from_secs(120), request_regenerate_config())).is_err() {
-                println!("Timed out waiting for user input.");
-            }
+/// Reads and parses the config file, without loading the state.
+fn read_config() -> Result<AppConfig, Error> {
+    let yaml_contents = match fs::read_to_string(FILE_NAME) {
+        Ok(c) => c,
+        Err(e) => return Err(Error::Config(format!("Config file {} was confirmed to exist, but could not be read: {}\nAre we missing permissions?", FILE_NAME, e))),
+    };
 
-            panic!();
+    return match serde_yaml::from_str::<AppConfig>(&yaml_contents) {
+        Ok(c) if c.branch_prefix.as_deref().is_some_and(|p| p.trim().is_empty()) => {
+            Err(Error::Config(format!("Config file {} sets an empty branch_prefix, which would make every branch of into_repo a mirror branch.", FILE_NAME)))
+        }
+        Ok(c) => Ok(c),
+        Err(_) => {
+            let problems: Vec<String> = validate::parse_problems(&yaml_contents).iter().map(|p| p.to_string()).collect();
+            Err(Error::Config(format!("Config file {} is invalid, run with 'validate' to check it against GitHub too:\n{}", FILE_NAME, problems.join("\n"))))
         }
     };
 }
 
-async fn get_bot_info(config: &AppConfig) -> Author {
-    let bot = Octocrab::builder()
-        .user_access_token(config.bot_token.clone())
-        .build()
-        .expect("Octocrab failed to build")
-        .current()
-        .user()
+async fn get_bot_info(config: &AppConfig) -> Result<Author, Error> {
+    let bot = forge::connect(&config.into_repo, &config.bot_token)?
+        .current_user()
         .await;
 
-    if bot.is_err() {
-        eprintln!("Couldn't obtain bot info: {}", bot.err().unwrap());
-        panic!("Failed to get bot info.");
-    }
-
-    return bot.unwrap();
-}
-
-async fn request_regenerate_config() {
-    println!("Config file {} is invalid, would you like to regenerate it? (THIS WILL DELETE THE CURRENT FILE) (y/N)", FILE_NAME);
-    let mut input = String::new();
-    match std::io::stdin().read_line(&mut input) {
-        Ok(_) => {
-            if input.trim().to_lowercase() == "y" {
-                input = String::new();
-                println!("Would you like to regenerate the file using a hardcoded template (with documentation) (1), or a default src-exact template (2)?");
-                match std::io::stdin().read_line(&mut input) {
-                    Ok(_) => {
-                        if input.trim().to_lowercase() == "2" {
-                            write_to_config(serde_yaml::to_string(&AppConfig::default()).unwrap(), None);
-                        } else {
-                            write_to_config(YAML_TEMPLATE.to_string(), None);
-                        }
-                        panic!("Config file {} has been regenerated. Please fill in the necessary information and run the program again.", FILE_NAME);
-                    }
-                    Err(e) => {
-                        panic!("Failed to read input: {}", e);
-                    }
-                }
-            } else {
-                panic!("Config file {} is invalid, and will not be regenerated.", FILE_NAME);
-            }
-        }
+    return match bot {
+        Ok(b) => Ok(b),
         Err(e) => {
-            panic!("Failed to read input: {}", e);
+            error!("Couldn't obtain bot info: {}", e);
+            Err(e)
         }
-    }
+    };
 }
 
-fn write_to_config(contents: String, config: Option<&AppConfig>) {
-    if let Some(c) = config {
-        if c.no_write.unwrap_or(false) {
-            println!("No-write flag is set, not overwriting config.");
-            println!("Contents would have been:\n{}", contents);
-            return;
-        }
-    }
-
-    fs::write(FILE_NAME, contents).expect(&format!("Config file {} could not be created, or could not be written to.\nAre we missing permissions?.", FILE_NAME));
+fn write_to_config(contents: String) -> Result<(), Error> {
+    return fs::write(FILE_NAME, contents)
+        .map_err(|e| Error::Config(format!("Config file {} could not be created, or could not be written to: {}\nAre we missing permissions?", FILE_NAME, e)));
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
 pub struct AppConfig {
     org_token: String,
     bot_token: String,
@@ -607,27 +1042,334 @@ pub struct AppConfig {
     #[serde(default)]
     issue_labels: Vec<String>,
     #[serde(default)]
+    label_map: LabelMap,
+    #[serde(default)]
+    pr_settings: PrSettings,
+    /// Only read to move into the state file, older versions of the bot kept it here.
+    #[serde(default, skip_serializing)]
+    assignee_rotation: Cell<usize>,
+    #[serde(default)]
     ignored_labels: Vec<String>,
     #[serde(default)]
     ignored_users: Vec<String>,
     #[serde(default)]
     prs_to_pull: Vec<u64>,
+    #[serde(default)]
+    skipped_prs: Vec<u64>,
+    /// Only read to move into the state file, older versions of the bot kept it here.
+    #[serde(default, skip_serializing)]
     time_offset: Option<NaiveTime>,
     #[serde(default)]
     hard_cap: Option<u32>,
     #[serde(default)]
     max_async: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stack_mode: Option<StackMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit_grouping: Option<CommitGrouping>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_action: Option<RevertAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reconcile: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stale_after_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cleanup_branches: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    push_mode: Option<PushMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remirror_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commands: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command_poll_minutes: Option<u32>,
+    /// Only read to move into the state file, older versions of the bot kept it here.
+    #[serde(default, skip_serializing)]
+    commands_checked_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transient_retries: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metrics_address: Option<String>,
+    #[serde(default)]
+    notifications: Vec<Notifier>,
+    #[serde(default)]
+    logging: LogSettings,
     #[serde(skip_serializing_if = "Option::is_none")]
     debug: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     no_write: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repo_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fetch_depth: Option<u32>,
+    /// A token to read `clone_repo` with instead of `org_token`, for when it's on a different GitHub.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clone_token: Option<String>,
+    /// Read from `state_file` after the config is loaded.
+    #[serde(skip)]
+    state: State,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone)]
 pub struct RepoInfo {
     owner: String,
     name: String,
     branch: String,
+    #[serde(default)]
+    forge: ForgeKind,
+    /// The API this repo is on, instead of api.github.com. For GitHub Enterprise Server this is like `https://github.example.com/api/v3`,
+    /// for Gitea like `https://codeberg.org/api/v1`, and for GitLab like `https://gitlab.example.com/api/v4`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_url: Option<String>,
+    /// Where this repo is cloned from, instead of github.com.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    git_host: Option<String>,
+}
+
+impl RepoInfo {
+    /// Where this repo is cloned from and browsed at. Gitea and GitLab serve both from the same host as their API.
+    fn host_url(&self) -> String {
+        if let Some(host) = &self.git_host {
+            return host.trim_end_matches('/').to_string();
+        }
+
+        return match (self.forge, &self.api_url) {
+            (ForgeKind::Gitea, Some(api)) => api.trim_end_matches('/').trim_end_matches("/api/v1").to_string(),
+            (ForgeKind::Gitlab, api) => api.as_deref().unwrap_or(forge::GITLAB_API_URL).trim_end_matches('/').trim_end_matches("/api/v4").to_string(),
+            _ => "https://github.com".to_string(),
+        };
+    }
+}
+
+/// Where and how the bot logs.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone)]
+pub struct LogSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    level: Option<String>,
+    #[serde(default)]
+    format: LogFormat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    directory: Option<String>,
+    #[serde(default)]
+    rotation: LogRotation,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+/// How often a new log file is started.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+/// Somewhere to send messages about finished runs and failed mirrors.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct Notifier {
+    kind: NotifierKind,
+    /// The webhook URL, or the homeserver URL for Matrix.
+    url: String,
+    /// The room ID to post in, for Matrix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    room: Option<String>,
+    /// The access token to post with, for Matrix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(default = "all_notify_events")]
+    events: Vec<NotifyEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    run_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    failure_template: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    Discord,
+    Slack,
+    Matrix,
+    /// A JSON POST with the message and the values that went into it.
+    Webhook,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// A run of `mirror_prs` finished.
+    Run,
+    /// An upstream PR failed to mirror and got a failure issue.
+    Failure,
+}
+
+fn all_notify_events() -> Vec<NotifyEvent> {
+    return vec![NotifyEvent::Run, NotifyEvent::Failure];
+}
+
+/// How upstream labels are translated into labels on mirror PRs.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone)]
+pub struct LabelMap {
+    #[serde(default)]
+    rules: Vec<LabelRule>,
+    #[serde(default)]
+    copy_existing: bool,
+    #[serde(default)]
+    create_missing: bool,
+    #[serde(default)]
+    size_labels: Vec<SizeLabel>,
+}
+
+/// Maps upstream labels matching either an exact name or a regex onto a target label.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct LabelRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+    to: String,
+}
+
+impl LabelRule {
+    fn regex(&self) -> Result<regex::Regex, regex::Error> {
+        return match (&self.from, &self.pattern) {
+            (Some(from), _) => regex::Regex::new(&format!("^{}$", regex::escape(from))),
+            (None, Some(pattern)) => regex::Regex::new(pattern),
+            (None, None) => Err(regex::Error::Syntax("label rules need either 'from' or 'pattern'".to_string())),
+        };
+    }
+}
+
+/// A label given to mirror PRs with at most `max_lines` added and deleted lines.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct SizeLabel {
+    #[serde(default)]
+    max_lines: Option<u64>,
+    label: String,
+}
+
+/// Settings for the PRs and issues the bot makes.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Clone)]
+pub struct PrSettings {
+    #[serde(default = "default_true")]
+    draft: bool,
+    #[serde(default)]
+    reviewers: Vec<String>,
+    #[serde(default)]
+    team_reviewers: Vec<String>,
+    #[serde(default)]
+    assignees: Vec<String>,
+    #[serde(default)]
+    assignee_mode: AssigneeMode,
+    #[serde(default)]
+    milestone: Option<u64>,
+}
+
+impl Default for PrSettings {
+    fn default() -> Self {
+        return PrSettings {
+            draft: true,
+            reviewers: Vec::new(),
+            team_reviewers: Vec::new(),
+            assignees: Vec::new(),
+            assignee_mode: AssigneeMode::default(),
+            milestone: None,
+        };
+    }
+}
+
+/// How assignees are picked from `pr_settings.assignees`.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssigneeMode {
+    /// Everyone in the list is assigned.
+    #[default]
+    Static,
+    /// One person from the list is assigned, taking turns.
+    RoundRobin,
+    /// Whoever the target repo's CODEOWNERS file says owns the changed files is assigned, falling back on the list.
+    Codeowners,
+}
+
+fn default_true() -> bool {
+    return true;
+}
+
+/// How mirror branches are based on each other.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StackMode {
+    /// Every mirror branch is made from the head of `into_repo.branch`.
+    #[default]
+    Never,
+    /// Every mirror branch is made on top of the previous mirror branch.
+    Always,
+    /// Mirror branches are made from `into_repo.branch`, and only retried on top of the previous mirror branch if the cherry-pick fails.
+    OnConflict,
+}
+
+/// How the commits of a plain git `clone_repo` are turned into mirror PRs.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitGrouping {
+    /// Every commit is mirrored on its own, merges are left out.
+    #[default]
+    Commit,
+    /// Only the branch's first parents are mirrored, so a merge brings along everything it merged.
+    Merge,
+}
+
+/// Where mirror branches are pushed to open mirror PRs from.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushMode {
+    /// Mirror branches are pushed to the bot's fork of `into_repo`, which is made on the first run.
+    #[default]
+    Fork,
+    /// Mirror branches are pushed straight into `into_repo` behind `branch_prefix`, so PR workflows get the repo's secrets.
+    SameRepo,
+}
+
+/// What to do when upstream reverts a PR we already mirrored.
+#[derive(Debug, serde::Serialize, serde::Deserialize, schemars::JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevertAction {
+    /// Mirror the revert like any other PR, linking it to the original mirror.
+    #[default]
+    Mirror,
+    /// Close the original mirror PR with a comment if it's still open, otherwise mirror the revert.
+    Close,
+}
+
+/// Other mirror PRs a new mirror PR should link to.
+#[derive(Default, Clone, Copy)]
+struct MirrorLinks {
+    depends_on: Option<u64>,
+    reverts: Option<u64>,
+}
+
+/// A mirror branch made this run, which later mirrors can be stacked onto.
+struct MirrorBase {
+    branch: String,
+    commit: Oid,
+    pr_number: Option<u64>,
+    pr_url: Option<String>,
 }
 
 impl AppConfig {
@@ -637,14 +1379,40 @@ impl AppConfig {
             .and_time(self.time_offset.unwrap_or(NaiveTime::default()));
     }
 
+    /// Only PRs merged after this are looked at.
+    fn cursor(&self) -> DateTime<Utc> {
+        return self.state.cursor.unwrap_or(self.date_from_with_time().and_utc());
+    }
+
+    fn is_skipped(&self, number: u64) -> bool {
+        return self.skipped_prs.contains(&number) || self.state.skipped_prs.contains(&number);
+    }
+
     fn days_between_interval(&self) -> Interval {
         return self.days_between.days();
     }
 
+    fn command_poll_interval(&self) -> Interval {
+        return self.command_poll_minutes.unwrap_or(10).max(1).minutes();
+    }
+
     fn days_between_days(&self) -> Days {
         return Days::new(self.days_between as u64);
     }
 
+    /// How many commits deep the local clone and its fetches go, if not all the way.
+    fn fetch_depth(&self) -> Option<u32> {
+        return self.fetch_depth.filter(|d| *d > 0);
+    }
+
+    fn transient_retries(&self) -> u32 {
+        return self.transient_retries.unwrap_or(2);
+    }
+
+    fn remirror_label(&self) -> String {
+        return self.remirror_label.clone().unwrap_or("remirror".to_string());
+    }
+
     fn get_repo_path(&self) -> String {
         let path = format!("{}_{}_into_{}_{}",
             self.clone_repo.owner,
@@ -653,7 +1421,101 @@ impl AppConfig {
             self.into_repo.name
         );
 
-        return path;
+        return match &self.repo_dir {
+            Some(dir) => Path::new(dir).join(path).to_string_lossy().into_owned(),
+            None => path,
+        };
+    }
+
+    /// Where the bare copy of a plain git `clone_repo` is kept.
+    fn source_path(&self) -> PathBuf {
+        return PathBuf::from(format!("{}_source", self.get_repo_path()));
+    }
+
+    /// The head of a plain git `clone_repo` the last finished run gathered. Commits after it are mirrored no matter their dates.
+    fn last_commit(&self) -> Option<&str> {
+        if self.clone_repo.forge != ForgeKind::Git {
+            return None;
+        }
+
+        return self.state.last_commit.as_deref();
+    }
+
+    /// The forge `clone_repo` is on, which is just `octocrab` unless upstream is on a different forge or has its own token.
+    fn upstream(&self, octocrab: &Octocrab) -> Result<Box<dyn Forge>, Error> {
+        if self.clone_repo.forge == ForgeKind::Git {
+            let grouping = self.commit_grouping.unwrap_or_default();
+            let token = self.clone_token.as_ref().unwrap_or(&self.org_token).clone();
+            return Ok(Box::new(forge::git::Git::new(&self.clone_repo, self.source_path(), grouping, self.state.last_commit.clone(), token)));
+        }
+
+        if self.target_on_github() && self.clone_repo.forge == ForgeKind::Github
+            && self.clone_repo.api_url == self.into_repo.api_url && self.clone_token.is_none() {
+            return Ok(Box::new(octocrab.clone()));
+        }
+
+        return forge::connect(&self.clone_repo, self.clone_token.as_ref().unwrap_or(&self.org_token));
+    }
+
+    /// The forge `into_repo` is on, which is just `octocrab` on GitHub.
+    fn target(&self, octocrab: &Octocrab) -> Result<Box<dyn Forge>, Error> {
+        if self.target_on_github() {
+            return Ok(Box::new(octocrab.clone()));
+        }
+
+        return forge::connect(&self.into_repo, &self.org_token);
+    }
+
+    /// Whether mirror branches are pushed to the bot's fork, rather than straight into `into_repo`.
+    fn pushes_to_fork(&self) -> bool {
+        return self.push_mode.unwrap_or_default() == PushMode::Fork;
+    }
+
+    /// What the names of mirror branches start with. Branches on the bot's fork don't need one.
+    fn branch_prefix(&self) -> &str {
+        if self.pushes_to_fork() {
+            return "";
+        }
+
+        return self.branch_prefix.as_deref().unwrap_or("mirror/");
+    }
+
+    /// What the head labels of mirror PRs start with, the owner of the repo their branches are in and the branch prefix.
+    fn mirror_head_prefix(&self, bot_info: &Author) -> String {
+        return match self.pushes_to_fork() {
+            true => format!("{}:", bot_info.login),
+            false => format!("{}:{}", self.into_repo.owner, self.branch_prefix()),
+        };
+    }
+
+    /// Whether `into_repo` is on GitHub. Reviewers, milestones, reverts, reconciling and commands only work there.
+    fn target_on_github(&self) -> bool {
+        return self.into_repo.forge == ForgeKind::Github;
+    }
+
+    /// The settings in use that only work when `into_repo` is on GitHub.
+    fn github_only_settings(&self) -> Vec<&'static str> {
+        if self.target_on_github() {
+            return Vec::new();
+        }
+
+        let settings = &self.pr_settings;
+        return [
+            ("pr_settings.reviewers", !settings.reviewers.is_empty() || !settings.team_reviewers.is_empty()),
+            ("pr_settings.assignees on PRs", !settings.assignees.is_empty()),
+            ("pr_settings.assignee_mode: codeowners", settings.assignee_mode == AssigneeMode::Codeowners),
+            ("pr_settings.milestone on PRs", settings.milestone.is_some()),
+            ("label_map.copy_existing", self.label_map.copy_existing),
+            ("label_map.create_missing", self.label_map.create_missing),
+            ("revert_action: close", self.revert_action == Some(RevertAction::Close)),
+            ("cleanup_branches", self.cleanup_branches == Some(true)),
+            ("reconcile", self.reconcile.unwrap_or(false)),
+            ("commands", self.commands.unwrap_or(false)),
+        ]
+        .into_iter()
+        .filter(|(_, used)| *used)
+        .map(|(name, _)| name)
+        .collect();
     }
 }
 
@@ -666,65 +1528,56 @@ impl Default for AppConfig {
                 owner: "space-wizards".to_string(),
                 name: "space-station-14".to_string(),
                 branch: "master".to_string(),
+                forge: ForgeKind::Github,
+                api_url: None,
+                git_host: None,
             },
             into_repo: RepoInfo {
                 owner: "Simple-Station".to_string(),
                 name: "Parkstation".to_string(),
                 branch: "master".to_string(),
+                forge: ForgeKind::Github,
+                api_url: None,
+                git_host: None,
             },
             date_from: NaiveDate::from_ymd_opt(2006, 6, 17).unwrap(),
             days_between: 7,
             pr_labels: Vec::new(),
             issue_labels: Vec::new(),
+            label_map: LabelMap::default(),
+            pr_settings: PrSettings::default(),
+            assignee_rotation: Cell::new(0),
             ignored_labels: Vec::new(),
             ignored_users: Vec::new(),
             prs_to_pull: Vec::new(),
+            skipped_prs: Vec::new(),
             time_offset: None,
             hard_cap: None,
             max_async: None,
+            stack_mode: None,
+            commit_grouping: None,
+            revert_action: None,
+            reconcile: None,
+            stale_after_days: None,
+            cleanup_branches: None,
+            push_mode: None,
+            branch_prefix: None,
+            remirror_label: None,
+            commands: None,
+            command_poll_minutes: None,
+            commands_checked_at: None,
+            transient_retries: None,
+            report_dir: None,
+            metrics_address: None,
+            notifications: Vec::new(),
+            logging: LogSettings::default(),
             debug: None,
             no_write: None,
+            state_file: None,
+            repo_dir: None,
+            fetch_depth: None,
+            clone_token: None,
+            state: State::default(),
         };
     }
 }
-
-#[derive(Debug)]
-enum Error {
-    Octocrab(OctoError),
-    Git(GitError),
-    General(String),
-}
-
-impl core::fmt::Display for Error {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match self {
-            Error::Octocrab(e) => write!(f, "Octocrab error: {}", e),
-            Error::Git(e) => write!(f, "Git error: {}", e),
-            Error::General(e) => write!(f, "General error: {}", e),
-        }
-    }
-}
-
-impl From<OctoError> for Error {
-    fn from(e: OctoError) -> Self {
-        return Error::Octocrab(e);
-    }
-}
-
-impl From<GitError> for Error {
-    fn from(e: GitError) -> Self {
-        return Error::Git(e);
-    }
-}
-
-impl From<String> for Error {
-    fn from(e: String) -> Self {
-        return Error::General(e);
-    }
-}
-
-impl From<&str> for Error {
-    fn from(e: &str) -> Self {
-        return Error::General(e.to_string());
-    }
-}
@@ -0,0 +1,230 @@
+use crate::Error;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use tracing::{debug, error, info, span, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// How long the scheduler loop can go without ticking before `/healthz` reports it as unhealthy, unless a task is running.
+const HEALTHY_TICK_SECONDS: i64 = 60;
+/// How long a single task can run before `/healthz` assumes it's stuck, on a network call or a fetch that never returns.
+const HEALTHY_TASK_SECONDS: i64 = 6 * 60 * 60;
+/// How long a metrics client gets to send its request or read the answer before it's dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Everything exposed on `/metrics`.
+struct Metrics {
+    mirrored: u64,
+    skipped: BTreeMap<String, u64>,
+    failed: BTreeMap<&'static str, u64>,
+    issues_created: u64,
+    api_calls: u64,
+    rate_limit_remaining: Option<u64>,
+    last_success: Option<DateTime<Utc>>,
+    next_run: Option<DateTime<Utc>>,
+    last_tick: Option<DateTime<Utc>>,
+    /// When the scheduled task running right now started. The loop doesn't tick while it runs.
+    task_started: Option<DateTime<Utc>>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    mirrored: 0,
+    skipped: BTreeMap::new(),
+    failed: BTreeMap::new(),
+    issues_created: 0,
+    api_calls: 0,
+    rate_limit_remaining: None,
+    last_success: None,
+    next_run: None,
+    last_tick: None,
+    task_started: None,
+});
+
+fn update(f: impl FnOnce(&mut Metrics)) {
+    if let Ok(mut metrics) = METRICS.lock() {
+        f(&mut metrics);
+    }
+}
+
+pub fn record_mirrored() {
+    update(|m| m.mirrored += 1);
+}
+
+pub fn record_skipped(reason: &str) {
+    update(|m| *m.skipped.entry(reason.to_string()).or_default() += 1);
+}
+
+pub fn record_failed(error: &Error) {
+    update(|m| *m.failed.entry(error.variant()).or_default() += 1);
+}
+
+pub fn record_issue() {
+    update(|m| m.issues_created += 1);
+}
+
+pub fn set_rate_limit_remaining(remaining: u64) {
+    update(|m| m.rate_limit_remaining = Some(remaining));
+}
+
+pub fn set_last_success(time: DateTime<Utc>) {
+    update(|m| m.last_success = Some(time));
+}
+
+pub fn set_next_run(time: DateTime<Utc>) {
+    update(|m| m.next_run = Some(time));
+}
+
+/// Marks the scheduler loop as alive, for `/healthz`.
+pub fn tick() {
+    update(|m| m.last_tick = Some(Utc::now()));
+}
+
+/// Marks a scheduled task as running until it's dropped, so `/healthz` doesn't mistake a long run for a stalled loop.
+pub struct RunningTask;
+
+pub fn task_started() -> RunningTask {
+    update(|m| m.task_started = Some(Utc::now()));
+    return RunningTask;
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        update(|m| {
+            m.task_started = None;
+            // The loop gets a full window to tick again once the task is done.
+            m.last_tick = Some(Utc::now());
+        });
+    }
+}
+
+/// Counts the requests octocrab makes to GitHub, going by the `HTTP` span it opens for each of them.
+pub struct ApiCallLayer;
+
+impl<S: Subscriber> Layer<S> for ApiCallLayer {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+        if attrs.metadata().name() == "HTTP" {
+            update(|m| m.api_calls += 1);
+        }
+    }
+}
+
+/// Serves `/metrics` and `/healthz` on the given address from a background thread.
+pub fn serve(address: &str) {
+    let listener = match TcpListener::bind(address) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Failed to listen for metrics on {}: {}", address, e);
+            return;
+        }
+    };
+
+    info!("Serving metrics on http://{}/metrics.", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(s) => {
+                    let _ = handle_connection(s).inspect_err(|e| debug!("Metrics connection failed: {}", e));
+                }
+                Err(e) => debug!("Failed to accept metrics connection: {}", e),
+            }
+        }
+    });
+}
+
+fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    // Connections are handled one at a time, so a client that never sends anything can't be left to hold up the rest.
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", render()),
+        ("GET", "/healthz") => match healthy() {
+            true => ("200 OK", "text/plain", "ok\n".to_string()),
+            false => ("503 Service Unavailable", "text/plain", "scheduler stalled\n".to_string()),
+        },
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    };
+
+    return write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body);
+}
+
+/// Whether the scheduler loop has ticked recently, or is busy with a task that hasn't run for too long.
+/// Before it first ticks, the bot is still starting up and counts as healthy.
+fn healthy() -> bool {
+    let m = match METRICS.lock() {
+        Ok(m) => m,
+        Err(_) => return false,
+    };
+
+    return match m.task_started {
+        Some(started) => (Utc::now() - started).num_seconds() < HEALTHY_TASK_SECONDS,
+        None => m.last_tick.is_none_or(|t| (Utc::now() - t).num_seconds() < HEALTHY_TICK_SECONDS),
+    };
+}
+
+/// Renders the metrics in the Prometheus text format.
+fn render() -> String {
+    let m = match METRICS.lock() {
+        Ok(m) => m,
+        Err(_) => return String::new(),
+    };
+
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# HELP mirror_bot_prs_mirrored_total Upstream PRs mirrored.\n# TYPE mirror_bot_prs_mirrored_total counter");
+    let _ = writeln!(out, "mirror_bot_prs_mirrored_total {}", m.mirrored);
+
+    let _ = writeln!(out, "# HELP mirror_bot_prs_skipped_total Upstream PRs filtered out of runs, by reason.\n# TYPE mirror_bot_prs_skipped_total counter");
+    for (reason, count) in &m.skipped {
+        let _ = writeln!(out, "mirror_bot_prs_skipped_total{{reason=\"{}\"}} {}", escape_label(reason), count);
+    }
+
+    let _ = writeln!(out, "# HELP mirror_bot_prs_failed_total Upstream PRs that failed to mirror, by error.\n# TYPE mirror_bot_prs_failed_total counter");
+    for (variant, count) in &m.failed {
+        let _ = writeln!(out, "mirror_bot_prs_failed_total{{error=\"{}\"}} {}", variant, count);
+    }
+
+    let _ = writeln!(out, "# HELP mirror_bot_issues_created_total Failure issues opened.\n# TYPE mirror_bot_issues_created_total counter");
+    let _ = writeln!(out, "mirror_bot_issues_created_total {}", m.issues_created);
+
+    let _ = writeln!(out, "# HELP mirror_bot_github_api_calls_total Requests made to the GitHub API.\n# TYPE mirror_bot_github_api_calls_total counter");
+    let _ = writeln!(out, "mirror_bot_github_api_calls_total {}", m.api_calls);
+
+    if let Some(remaining) = m.rate_limit_remaining {
+        let _ = writeln!(out, "# HELP mirror_bot_github_rate_limit_remaining GitHub API requests left in the current rate limit window.\n# TYPE mirror_bot_github_rate_limit_remaining gauge");
+        let _ = writeln!(out, "mirror_bot_github_rate_limit_remaining {}", remaining);
+    }
+
+    if let Some(time) = m.last_success {
+        let _ = writeln!(out, "# HELP mirror_bot_last_success_timestamp_seconds When the last run finished without stopping early.\n# TYPE mirror_bot_last_success_timestamp_seconds gauge");
+        let _ = writeln!(out, "mirror_bot_last_success_timestamp_seconds {}", time.timestamp());
+    }
+
+    if let Some(time) = m.next_run {
+        let _ = writeln!(out, "# HELP mirror_bot_next_run_timestamp_seconds When the next run is scheduled.\n# TYPE mirror_bot_next_run_timestamp_seconds gauge");
+        let _ = writeln!(out, "mirror_bot_next_run_timestamp_seconds {}", time.timestamp());
+    }
+
+    return out;
+}
+
+fn escape_label(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+}
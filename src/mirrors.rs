@@ -0,0 +1,122 @@
+use crate::{git_utils, pr_template::PrTemplate, AppConfig, Error};
+use git2::Repository;
+use octocrab::{models::{pulls::PullRequest, Author, IssueState}, params, Octocrab};
+use std::collections::HashSet;
+use tokio::sync::OnceCell;
+use tracing::{error, info, warn};
+
+/// A PR the bot opened on `into_repo`, and the upstream PR it mirrors.
+#[derive(Debug, Clone)]
+pub struct MirrorPr {
+    pub pr: PullRequest,
+    pub upstream_number: u64,
+    pub upstream_title: String,
+    pub upstream_sha: String,
+}
+
+impl MirrorPr {
+    pub fn is_open(&self) -> bool {
+        return self.pr.state == Some(IssueState::Open);
+    }
+
+    pub fn is_merged(&self) -> bool {
+        return self.pr.merged_at.is_some();
+    }
+}
+
+/// The bot's mirror PRs, listed the first time something in a run needs them and shared by the rest of the run.
+///
+/// Paging through every PR of `into_repo` is slow on big repos, so reverts, cleanup and reconciling all use this one listing.
+#[derive(Debug, Default)]
+pub struct MirrorList {
+    mirrors: OnceCell<Vec<MirrorPr>>,
+}
+
+impl MirrorList {
+    pub async fn get(&self, octocrab: &Octocrab, config: &AppConfig, bot_info: &Author) -> Result<&[MirrorPr], Error> {
+        let mirrors = self.mirrors.get_or_try_init(|| get_mirror_prs(octocrab, config, bot_info)).await?;
+        return Ok(mirrors.as_slice());
+    }
+}
+
+/// Gets every mirror PR the bot has opened on `into_repo`, in any state.
+pub async fn get_mirror_prs(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author) -> Result<Vec<MirrorPr>, Error> {
+    let first_page = octocrab
+        .pulls(&config.into_repo.owner, &config.into_repo.name)
+        .list()
+        .state(params::State::All)
+        .base(&config.into_repo.branch)
+        .per_page(100)
+        .send()
+        .await?;
+
+    let all_prs = octocrab.all_pages(first_page).await?;
+    let logins = bot_logins(octocrab, bot_info).await;
+
+    return Ok(all_prs.into_iter().filter_map(|pr| to_mirror(pr, config, bot_info, &logins)).collect());
+}
+
+/// The accounts the bot acts as. PRs and issues are opened with `org_token`, which may belong to
+/// someone other than the `bot_token` account that pushes the branches.
+pub async fn bot_logins(octocrab: &Octocrab, bot_info: &Author) -> Vec<String> {
+    let mut logins = vec![bot_info.login.clone()];
+
+    match octocrab.current().user().await {
+        Ok(user) if user.login != bot_info.login => logins.push(user.login),
+        Ok(_) => {}
+        Err(e) => warn!("Failed to get the account behind org_token, only PRs by {} count as mirrors: {}", bot_info.login, e),
+    }
+
+    return logins;
+}
+
+/// Turns a PR into a mirror PR if it was made by the bot.
+pub fn to_mirror(pr: PullRequest, config: &AppConfig, bot_info: &Author, logins: &[String]) -> Option<MirrorPr> {
+    if !pr.user.as_ref().is_some_and(|u| logins.contains(&u.login)) {
+        return None;
+    }
+
+    let head_prefix = config.mirror_head_prefix(bot_info);
+    if !pr.head.label.as_ref().is_some_and(|l| l.starts_with(&head_prefix)) {
+        return None;
+    }
+
+    let title = pr.title.clone().unwrap_or_default();
+    let upstream_number = PrTemplate::number_from_title(&title)?;
+    let upstream_title = PrTemplate::original_title_from_title(&title).unwrap_or_default();
+    let upstream_sha = PrTemplate::sha_from_body(&pr.body.clone().unwrap_or_default()).unwrap_or_default();
+
+    return Some(MirrorPr { pr, upstream_number, upstream_title, upstream_sha });
+}
+
+/// The branches of mirror PRs that were merged or closed, leaving out any an open mirror PR still uses.
+pub fn stale_branches(mirrors: &[MirrorPr]) -> Vec<String> {
+    let in_use: HashSet<&str> = mirrors.iter().filter(|m| m.is_open()).map(|m| m.pr.head.ref_field.as_str()).collect();
+
+    let mut stale: Vec<String> = mirrors
+        .iter()
+        .filter(|m| !m.is_open() && !in_use.contains(m.pr.head.ref_field.as_str()))
+        .map(|m| m.pr.head.ref_field.clone())
+        .collect();
+    stale.sort();
+    stale.dedup();
+
+    return stale;
+}
+
+/// Deletes the branches of merged and closed mirror PRs from wherever they were pushed.
+pub async fn cleanup_branches(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, repo: &Repository, mirrors: &MirrorList) {
+    let mirrors = match mirrors.get(octocrab, config, bot_info).await {
+        Ok(m) => m,
+        Err(e) => {
+            error!("Failed to get mirror PRs to clean up branches after: {}", e);
+            return;
+        }
+    };
+
+    match git_utils::delete_mirror_branches(repo, config, bot_info, &stale_branches(mirrors)) {
+        Ok(0) => {}
+        Ok(deleted) => info!("Deleted {} branches of merged or closed mirror PRs.", deleted),
+        Err(e) => error!("Failed to delete stale mirror branches: {}", e),
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{report::{Decision, RunReport}, AppConfig, Error, Notifier, NotifierKind, NotifyEvent, NO_NET_ACTIVITY};
+use octocrab::models::pulls::PullRequest;
+use std::{collections::BTreeMap, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+use tracing::{debug, warn};
+
+/// The longest message Discord accepts.
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+const DEFAULT_RUN_TEMPLATE: &str = "Mirrored {mirrored} and failed {failed} PRs from {clone_repo} into {into_repo}, skipping {skipped}.\n{links}";
+const DEFAULT_FAILURE_TEMPLATE: &str = "Failed to mirror {clone_repo}#{number} ({title}) into {into_repo}: {error}\n{issue_url}";
+
+/// Makes Matrix transaction IDs unique within a run of the bot.
+static MATRIX_TRANSACTION: AtomicU64 = AtomicU64::new(0);
+
+/// Tells every notifier listening for `run` how a run went.
+pub async fn run_finished(config: &AppConfig, report: &RunReport) {
+    let mut values = common_values(config);
+    values.insert("mirrored", report.count(Decision::Mirrored).to_string());
+    values.insert("failed", report.count(Decision::Failed).to_string());
+    values.insert("skipped", report.count(Decision::Filtered).to_string());
+    values.insert("links", report.links().join("\n"));
+
+    send_all(config, NotifyEvent::Run, values).await;
+}
+
+/// Tells every notifier listening for `failure` that an upstream PR failed to mirror.
+pub async fn mirror_failed(config: &AppConfig, pr: &PullRequest, error: &Error, issue_url: Option<&str>) {
+    let mut values = common_values(config);
+    values.insert("number", pr.number.to_string());
+    values.insert("title", pr.title.clone().unwrap_or_default());
+    values.insert("error", error.to_string());
+    values.insert("upstream_url", pr.html_url.as_ref().map(|u| u.to_string()).unwrap_or_default());
+    values.insert("issue_url", issue_url.unwrap_or_default().to_string());
+
+    send_all(config, NotifyEvent::Failure, values).await;
+}
+
+fn common_values(config: &AppConfig) -> BTreeMap<&'static str, String> {
+    return BTreeMap::from([
+        ("clone_repo", format!("{}/{}", config.clone_repo.owner, config.clone_repo.name)),
+        ("into_repo", format!("{}/{}", config.into_repo.owner, config.into_repo.name)),
+    ]);
+}
+
+async fn send_all(config: &AppConfig, event: NotifyEvent, values: BTreeMap<&'static str, String>) {
+    let notifiers: Vec<&Notifier> = config.notifications.iter().filter(|n| n.events.contains(&event)).collect();
+    if notifiers.is_empty() {
+        return;
+    }
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(10)).build() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Failed to set up the client for notifications: {}", e);
+            return;
+        }
+    };
+
+    for notifier in notifiers {
+        let template = match event {
+            NotifyEvent::Run => notifier.run_template.as_deref().unwrap_or(DEFAULT_RUN_TEMPLATE),
+            NotifyEvent::Failure => notifier.failure_template.as_deref().unwrap_or(DEFAULT_FAILURE_TEMPLATE),
+        };
+        let text = render(template, &values);
+
+        debug!("Sending {:?} notification to {:?} notifier:\n{}", event, notifier.kind, text);
+
+        if NO_NET_ACTIVITY {
+            continue;
+        }
+
+        let _ = send(&client, notifier, event, &text, &values)
+            .await
+            .inspect_err(|e| warn!("Failed to send {:?} notification to {:?} notifier: {}", event, notifier.kind, e));
+    }
+}
+
+async fn send(client: &reqwest::Client, notifier: &Notifier, event: NotifyEvent, text: &str, values: &BTreeMap<&'static str, String>) -> Result<(), reqwest::Error> {
+    let request = match notifier.kind {
+        NotifierKind::Discord => {
+            let content: String = text.chars().take(DISCORD_MAX_LENGTH).collect();
+            client.post(&notifier.url).json(&serde_json::json!({ "content": content }))
+        }
+        NotifierKind::Slack => client.post(&notifier.url).json(&serde_json::json!({ "text": text })),
+        NotifierKind::Matrix => {
+            let transaction = format!("mirror-bot-{}-{}", chrono::Utc::now().timestamp_millis(), MATRIX_TRANSACTION.fetch_add(1, Ordering::Relaxed));
+            let url = format!("{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+                notifier.url.trim_end_matches('/'),
+                notifier.room.as_deref().unwrap_or_default(),
+                transaction);
+
+            client
+                .put(url)
+                .bearer_auth(notifier.token.as_deref().unwrap_or_default())
+                .json(&serde_json::json!({ "msgtype": "m.text", "body": text }))
+        }
+        NotifierKind::Webhook => client.post(&notifier.url).json(&serde_json::json!({
+            "event": event,
+            "text": text,
+            "data": values,
+        })),
+    };
+
+    request.send().await?.error_for_status()?;
+    return Ok(());
+}
+
+/// Fills in `{name}` placeholders in a template, leaving unknown ones as they are.
+fn render(template: &str, values: &BTreeMap<&'static str, String>) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+
+    return text.trim_end().to_string();
+}
@@ -29,6 +29,8 @@ pub struct PrTemplate {
     merge_user_icon: String,
     open_date: String,
     merge_date: String,
+    depends_on: String,
+    reverts: String,
 }
 
 impl PrTemplate {
@@ -132,6 +134,59 @@ impl PrTemplate {
         return template;
     }
 
+    /// Marks this mirror as stacked on top of another mirror PR.
+    pub fn set_depends_on(&mut self, number: u64) {
+        self.depends_on = number.to_string();
+    }
+
+    /// Marks this mirror as reverting another mirror PR.
+    pub fn set_reverts(&mut self, number: u64) {
+        self.reverts = number.to_string();
+    }
+
+    /// Gets the upstream PR number back out of a title made by `get_title`.
+    pub fn number_from_title(title: &str) -> Option<u64> {
+        return title.strip_prefix("Mirror ")?.split_once(':')?.0.parse().ok();
+    }
+
+    /// Gets the upstream PR title back out of a title made by `get_title`.
+    pub fn original_title_from_title(title: &str) -> Option<String> {
+        return Some(title.strip_prefix("Mirror ")?.split_once(": ")?.1.to_string());
+    }
+
+    /// Gets the upstream merge sha back out of a body made by `get_body`.
+    pub fn sha_from_body(body: &str) -> Option<String> {
+        return body
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("###### `")?.strip_suffix('`'))
+            .map(|s| s.to_string());
+    }
+
+    /// Callouts about how this mirror relates to other mirrors.
+    fn get_notes(&self) -> String {
+        let mut notes = String::new();
+
+        if !self.depends_on.is_empty() {
+            notes += &format!("> [!IMPORTANT]\n> This mirror is stacked on top of #{} and contains its changes, merge that one first.\n\n", self.depends_on);
+        }
+
+        if !self.reverts.is_empty() {
+            notes += &format!("> [!WARNING]\n> This is an upstream revert of the changes mirrored in #{}.\n\n", self.reverts);
+        }
+
+        return notes;
+    }
+
+    /// The title of the issue made when mirroring this PR fails.
+    pub fn get_failure_title(&self) -> String {
+        return format!("Failed to cherry-pick PR #{}: {}", self.number, self.title);
+    }
+
+    /// Gets the upstream PR number back out of a title made by `get_failure_title`.
+    pub fn number_from_failure_title(title: &str) -> Option<u64> {
+        return title.strip_prefix("Failed to cherry-pick PR #")?.split_once(':')?.0.parse().ok();
+    }
+
     pub fn get_title(&self) -> String {
         return format!("Mirror {}: {}", self.number, self.title);
     }
@@ -142,6 +197,7 @@ impl PrTemplate {
             \n\
             ###### `{merge_sha}`\n\
             \n\
+            {notes}\
             PR opened by <img src=\"{open_user_icon}\" width=\"16\"/><a href=\"{open_user_link}\"> {open_user_name}</a> at {open_date} - merged at {merge_date}\n\
             \n\
             ---\n\
@@ -178,6 +234,7 @@ impl PrTemplate {
             //-- PR merged by <img src=\"{merge_user_icon}\" width=\"16\"/><a href=\"{merge_user_link}\"> {merge_user_name}</a> at {merge_date}\n\
             // Turns out the 'author' of the PR is always GitHub webflow... :T
             merge_sha=self.merge_sha,
+            notes=self.get_notes(),
             original_desc=self.original_desc.split("\n").into_iter().map(|l| format!("> {}\n", l)).collect::<String>(),
             labels_list=self.labels.iter().map(|l| format!("- {}\n", l)).collect::<String>(),
             changed_files=self.changed_files,
@@ -216,6 +273,8 @@ impl Default for PrTemplate {
             merge_user_icon: String::new(),
             open_date: String::new(),
             merge_date: String::new(),
+            depends_on: String::new(),
+            reverts: String::new(),
         }
     }
 }
@@ -0,0 +1,203 @@
+use crate::{git_utils, mirrors::{MirrorList, MirrorPr}, report::PhaseTimings, AppConfig, Error, NO_NET_ACTIVITY};
+use chrono::{Duration as ChronoDuration, Utc};
+use octocrab::{models::Author, params, Octocrab};
+use tracing::{error, info, warn};
+
+/// What happened to a mirror PR after the bot opened it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Merged,
+    /// Closed without being merged.
+    Closed,
+    Open,
+    /// Still open, but nobody has touched it in `stale_after_days`.
+    Stale,
+}
+
+/// Checks every mirror PR the bot has made, reports what became of them,
+/// and re-mirrors any that were given the `remirror_label`.
+pub async fn reconcile(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, mirrors: &MirrorList) {
+    info!("Reconciling mirror PRs on {}/{}.", config.into_repo.owner, config.into_repo.name);
+
+    let mirrors = match mirrors.get(octocrab, config, bot_info).await {
+        Ok(m) => m,
+        Err(e) => {
+            error!("Failed to get mirror PRs for reconciliation: {}", e);
+            return;
+        }
+    };
+
+    let outcomes: Vec<(&MirrorPr, Outcome)> = mirrors.iter().map(|m| (m, outcome(m, config))).collect();
+    report_drift(&outcomes);
+
+    let remirror_label = config.remirror_label();
+    let to_remirror: Vec<&MirrorPr> = outcomes
+        .iter()
+        .filter(|(m, o)| *o != Outcome::Merged && m.pr.labels.as_ref().is_some_and(|l| l.iter().any(|l| l.name == remirror_label)))
+        .map(|(m, _)| *m)
+        .collect();
+
+    if to_remirror.is_empty() {
+        return;
+    }
+
+    info!("Re-mirroring {} PRs labeled '{}'.", to_remirror.len(), remirror_label);
+
+    let repo = match git_utils::ensure_repo(config, bot_info) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to get or create local repository for re-mirroring: {}", e);
+            return;
+        }
+    };
+
+    for mirror in to_remirror {
+        remirror(&repo, octocrab, config, bot_info, mirror).await;
+
+        if git_utils::reset_repo(&repo, config).is_err() {
+            error!("Failed to reset repository after re-mirroring PR #{}.", mirror.upstream_number);
+            return;
+        }
+    }
+}
+
+fn outcome(mirror: &MirrorPr, config: &AppConfig) -> Outcome {
+    if mirror.is_merged() {
+        return Outcome::Merged;
+    }
+
+    if !mirror.is_open() {
+        return Outcome::Closed;
+    }
+
+    let stale_cutoff = Utc::now() - ChronoDuration::days(config.stale_after_days.unwrap_or(14) as i64);
+    if mirror.pr.updated_at.is_some_and(|u| u < stale_cutoff) {
+        return Outcome::Stale;
+    }
+
+    return Outcome::Open;
+}
+
+fn report_drift(outcomes: &[(&MirrorPr, Outcome)]) {
+    let count = |outcome: Outcome| outcomes.iter().filter(|(_, o)| *o == outcome).count();
+
+    info!("Mirror PRs: {} merged, {} closed without merging, {} open, {} stale.",
+        count(Outcome::Merged), count(Outcome::Closed), count(Outcome::Open), count(Outcome::Stale));
+
+    for (mirror, outcome) in outcomes {
+        match outcome {
+            Outcome::Closed => info!("Mirror #{} of upstream PR #{} was closed without merging.", mirror.pr.number, mirror.upstream_number),
+            Outcome::Stale => info!("Mirror #{} of upstream PR #{} is stale, last updated {}.",
+                mirror.pr.number, mirror.upstream_number, mirror.pr.updated_at.map(|u| u.to_string()).unwrap_or_default()),
+            _ => {}
+        }
+    }
+}
+
+/// Regenerates a mirror from the current target branch as a new PR, and retires the old one once that worked.
+///
+/// If it fails, the old PR is left as it was along with its label, so it's tried again next time.
+async fn remirror(repo: &git2::Repository, octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, mirror: &MirrorPr) {
+    info!("Re-mirroring upstream PR #{} (was #{}).", mirror.upstream_number, mirror.pr.number);
+
+    let upstream = match config.upstream(octocrab) {
+        Ok(u) => u,
+        Err(e) => {
+            error!("Failed to set up a client for {}/{}: {}", config.clone_repo.owner, config.clone_repo.name, e);
+            return;
+        }
+    };
+
+    let upstream_pr = match upstream.get_pr(&config.clone_repo, mirror.upstream_number).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to get upstream PR #{}: {}", mirror.upstream_number, e);
+            return;
+        }
+    };
+
+    // Re-mirrored the same day it was opened, the new branch would be the old PR's own, which GitHub won't open a second PR from.
+    // Force pushing the regenerated branch updates the old PR in place instead.
+    if mirror.is_open() && crate::mirror_branch_name(config, &upstream_pr) == mirror.pr.head.ref_field {
+        let regenerated = match upstream_pr.merge_commit_sha.as_deref() {
+            Some(sha) => crate::create_mirror_commit(repo, config, bot_info, &mirror.pr.head.ref_field, sha, None, &mut PhaseTimings::default())
+                .and_then(|_| git_utils::push_to_remote(repo, config, bot_info)),
+            None => Err(Error::from("The upstream PR has no merge commit")),
+        };
+
+        match regenerated {
+            Ok(()) => {
+                remove_remirror_label(octocrab, config, mirror).await;
+                comment(octocrab, config, mirror.pr.number, format!("Re-mirrored from the latest {} onto this PR's branch.", config.into_repo.branch)).await;
+            }
+            Err(e) => remirror_failed(octocrab, config, mirror, e).await,
+        }
+        return;
+    }
+
+    let span = crate::pr_span(&upstream_pr);
+    let new_mirror = match span.in_scope(|| crate::cherry_pick_and_push_pr(repo, octocrab, upstream_pr, config, bot_info, None, None, &mut PhaseTimings::default())) {
+        Ok(m) => m,
+        Err(e) => {
+            remirror_failed(octocrab, config, mirror, e).await;
+            return;
+        }
+    };
+
+    close_old_mirror(octocrab, config, mirror).await;
+
+    if let Some(number) = new_mirror.pr_number {
+        comment(octocrab, config, mirror.pr.number, format!("Re-mirrored from the latest {} as #{}.", config.into_repo.branch, number)).await;
+    }
+}
+
+/// Says why re-mirroring failed on the old PR, which keeps its label to be tried again.
+async fn remirror_failed(octocrab: &Octocrab, config: &AppConfig, mirror: &MirrorPr, error: Error) {
+    error!("Failed to re-mirror upstream PR #{}: {}", mirror.upstream_number, error);
+
+    comment(octocrab, config, mirror.pr.number, format!("Failed to re-mirror this from the latest {}, it will be tried again next time: {}\n\n{}",
+        config.into_repo.branch, error, error.guidance())).await;
+}
+
+async fn comment(octocrab: &Octocrab, config: &AppConfig, number: u64, body: String) {
+    if NO_NET_ACTIVITY {
+        return;
+    }
+
+    let _ = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .create_comment(number, body)
+        .await
+        .inspect_err(|e| warn!("Failed to comment on mirror PR #{}: {}", number, e));
+}
+
+async fn remove_remirror_label(octocrab: &Octocrab, config: &AppConfig, mirror: &MirrorPr) {
+    if NO_NET_ACTIVITY {
+        return;
+    }
+
+    let _ = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .remove_label(mirror.pr.number, config.remirror_label())
+        .await
+        .inspect_err(|e| warn!("Failed to remove remirror label from PR #{}: {}", mirror.pr.number, e));
+}
+
+/// Removes the remirror label from an old mirror PR and closes it if it's still open.
+async fn close_old_mirror(octocrab: &Octocrab, config: &AppConfig, mirror: &MirrorPr) {
+    if NO_NET_ACTIVITY {
+        return;
+    }
+
+    remove_remirror_label(octocrab, config, mirror).await;
+
+    if mirror.is_open() {
+        let _ = octocrab
+            .pulls(&config.into_repo.owner, &config.into_repo.name)
+            .update(mirror.pr.number)
+            .state(params::pulls::State::Closed)
+            .send()
+            .await
+            .inspect_err(|e| error!("Failed to close mirror PR #{}: {}", mirror.pr.number, e));
+    }
+}
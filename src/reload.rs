@@ -0,0 +1,168 @@
+use crate::{generate_config, read_config, AppConfig, Error, State, FILE_NAME};
+use serde_json::Value;
+use std::{fs, sync::Mutex, time::SystemTime};
+use tracing::{debug, error, info, warn};
+
+/// The last good config the scheduler runs on, replaced whenever the config file changes into something valid.
+static CURRENT: Mutex<Option<AppConfig>> = Mutex::new(None);
+
+/// What changed in a reloaded config that the scheduler loop has to act on.
+pub struct Reload {
+    pub schedule_changed: bool,
+}
+
+pub fn set(config: AppConfig) {
+    if let Ok(mut current) = CURRENT.lock() {
+        *current = Some(config);
+    }
+}
+
+/// The last good config, along with the state as it is right now.
+pub fn current() -> Result<AppConfig, Error> {
+    let config = match CURRENT.lock() {
+        Ok(c) => c.clone(),
+        Err(_) => None,
+    };
+
+    let mut config = match config {
+        Some(c) => c,
+        None => return generate_config(),
+    };
+
+    config.state = State::load(&config)?;
+    return Ok(config);
+}
+
+/// Notices when the config file is changed.
+pub struct Watcher {
+    modified: Option<SystemTime>,
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        return Watcher { modified: modified() };
+    }
+
+    /// Loads the config file again if it changed since the last look, and makes it the current config if it's valid.
+    pub fn poll(&mut self) -> Option<Reload> {
+        let modified = modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let old = match CURRENT.lock() {
+            Ok(c) => c.clone()?,
+            Err(_) => return None,
+        };
+
+        let new = match read_config().and_then(check) {
+            Ok(c) => c,
+            Err(e) => {
+                error!("{} changed, but the new config can't be used. Still running on the last good config. {}", FILE_NAME, e);
+                return None;
+            }
+        };
+
+        let changes = diff(&old, &new);
+        if changes.is_empty() {
+            debug!("{} was touched, but nothing in it changed.", FILE_NAME);
+            return None;
+        }
+
+        info!("Reloaded {}, changes:\n  {}", FILE_NAME, changes.join("\n  "));
+
+        if old.logging.level != new.logging.level || old.logging.format != new.logging.format
+            || old.logging.directory != new.logging.directory || old.logging.rotation != new.logging.rotation {
+            warn!("Changes to 'logging' only apply after the bot is restarted.");
+        }
+        if old.metrics_address != new.metrics_address {
+            warn!("Changes to 'metrics_address' only apply after the bot is restarted.");
+        }
+
+        let schedule_changed = old.days_between != new.days_between
+            || old.commands != new.commands
+            || old.command_poll_minutes != new.command_poll_minutes;
+
+        set(new);
+        return Some(Reload { schedule_changed });
+    }
+}
+
+fn modified() -> Option<SystemTime> {
+    return fs::metadata(FILE_NAME).and_then(|m| m.modified()).ok();
+}
+
+/// Rejects configs that parse, but can't be switched to while running on a schedule.
+fn check(config: AppConfig) -> Result<AppConfig, Error> {
+    if config.days_between == 0 {
+        return Err(Error::Config("'days_between' can't be changed to 0 while running on a schedule, restart the bot to run once.".to_string()));
+    }
+
+    return Ok(config);
+}
+
+/// Lists what changed between two configs, like `label_map.copy_existing: false -> true`. Tokens and webhook URLs are never shown.
+fn diff(old: &AppConfig, new: &AppConfig) -> Vec<String> {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+
+    let mut changes = Vec::new();
+    diff_values(&old, &new, "", &mut changes);
+    return changes;
+}
+
+fn diff_values(old: &Value, new: &Value, path: &str, changes: &mut Vec<String>) {
+    if old == new {
+        return;
+    }
+
+    if let (Value::Object(old_map), Value::Object(new_map)) = (old, new) {
+        let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        for key in keys {
+            let child_path = match path {
+                "" => key.clone(),
+                _ => format!("{}.{}", path, key),
+            };
+            diff_values(old_map.get(key).unwrap_or(&Value::Null), new_map.get(key).unwrap_or(&Value::Null), &child_path, changes);
+        }
+        return;
+    }
+
+    if is_secret(path.rsplit('.').next().unwrap_or(path)) {
+        changes.push(format!("{}: changed", path));
+        return;
+    }
+
+    changes.push(format!("{}: {} -> {}", path, show(old), show(new)));
+}
+
+fn is_secret(key: &str) -> bool {
+    return key.ends_with("token") || key == "url";
+}
+
+/// Shows a value on one line, hiding any secrets nested inside it.
+fn show(value: &Value) -> String {
+    let mut value = value.clone();
+    hide_tokens(&mut value);
+    return value.to_string();
+}
+
+fn hide_tokens(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if is_secret(key) && !child.is_null() {
+                    *child = Value::String("***".to_string());
+                } else {
+                    hide_tokens(child);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(hide_tokens),
+        _ => {}
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{metrics, AppConfig, Error};
+use chrono::{DateTime, Utc};
+use octocrab::models::pulls::PullRequest;
+use std::{fmt::Write, fs, path::Path, time::Instant};
+use tracing::{error, info};
+
+/// What happened during a single run of `mirror_prs`, written to `report_dir` as JSON and Markdown once the run is over.
+#[derive(Debug, serde::Serialize)]
+pub struct RunReport {
+    started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<DateTime<Utc>>,
+    clone_repo: String,
+    into_repo: String,
+    /// Only PRs merged between these two points are mirrored.
+    window_from: DateTime<Utc>,
+    window_to: DateTime<Utc>,
+    /// How long gathering upstream PRs took.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gather_seconds: Option<f64>,
+    /// How long fetching every upstream commit the run needed took.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fetch_seconds: Option<f64>,
+    /// What was wrong with the local repo before the run and got fixed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repairs: Vec<String>,
+    prs: Vec<PrReport>,
+    /// Errors that stopped the run rather than a single PR.
+    errors: Vec<String>,
+    /// The first of those errors, for deciding how a one-shot run exits.
+    #[serde(skip)]
+    fatal: Option<Error>,
+}
+
+/// What happened to a single upstream PR during a run.
+#[derive(Debug, serde::Serialize)]
+pub struct PrReport {
+    pub number: u64,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    pub decision: Decision,
+    /// Why the PR was filtered out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pr_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue_url: Option<String>,
+    pub timings: PhaseTimings,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    /// Considered, but the run stopped before getting to it.
+    Pending,
+    Filtered,
+    Mirrored,
+    Failed,
+}
+
+/// The seconds spent in each phase of mirroring a PR, summed over retries.
+#[derive(Debug, serde::Serialize, Default, Clone)]
+pub struct PhaseTimings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fetch: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cherry_pick: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    push: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pr_creation: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    Fetch,
+    CherryPick,
+    Push,
+    PrCreation,
+}
+
+impl PhaseTimings {
+    /// Runs `f`, adding the time it took to `phase`.
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed().as_secs_f64();
+
+        let slot = match phase {
+            Phase::Fetch => &mut self.fetch,
+            Phase::CherryPick => &mut self.cherry_pick,
+            Phase::Push => &mut self.push,
+            Phase::PrCreation => &mut self.pr_creation,
+        };
+        *slot = Some(slot.unwrap_or_default() + elapsed);
+
+        return result;
+    }
+}
+
+impl RunReport {
+    pub fn new(config: &AppConfig) -> Self {
+        let now = Utc::now();
+
+        return RunReport {
+            started_at: now,
+            finished_at: None,
+            clone_repo: format!("{}/{}/{}", config.clone_repo.owner, config.clone_repo.name, config.clone_repo.branch),
+            into_repo: format!("{}/{}/{}", config.into_repo.owner, config.into_repo.name, config.into_repo.branch),
+            window_from: config.cursor(),
+            window_to: now,
+            gather_seconds: None,
+            fetch_seconds: None,
+            repairs: Vec::new(),
+            prs: Vec::new(),
+            errors: Vec::new(),
+            fatal: None,
+        };
+    }
+
+    pub fn set_gather_time(&mut self, started: Instant) {
+        self.gather_seconds = Some(started.elapsed().as_secs_f64());
+    }
+
+    pub fn set_fetch_time(&mut self, started: Instant) {
+        self.fetch_seconds = Some(started.elapsed().as_secs_f64());
+    }
+
+    pub fn set_repairs(&mut self, repairs: Vec<String>) {
+        self.repairs = repairs;
+    }
+
+    /// Adds the gathered upstream PRs, all pending until something is decided about them.
+    pub fn consider(&mut self, prs: &[PullRequest]) {
+        self.prs.extend(prs.iter().map(|pr| PrReport {
+            number: pr.number,
+            title: pr.title.clone().unwrap_or_default(),
+            merged_at: pr.merged_at,
+            sha: pr.merge_commit_sha.clone(),
+            decision: Decision::Pending,
+            reason: None,
+            branch: None,
+            pr_url: None,
+            issue_url: None,
+            timings: PhaseTimings::default(),
+            error: None,
+        }));
+    }
+
+    /// Marks a PR as filtered out of the run.
+    pub fn filter(&mut self, number: u64, reason: &str) {
+        metrics::record_skipped(reason);
+        self.filter_old(number, reason);
+    }
+
+    /// Marks a PR from before the run's window as filtered out. Every run gathers these again, so they aren't counted in the metrics.
+    pub fn filter_old(&mut self, number: u64, reason: &str) {
+        if let Some(pr) = self.pr(number) {
+            pr.decision = Decision::Filtered;
+            pr.reason = Some(reason.to_string());
+        }
+    }
+
+    pub fn pr(&mut self, number: u64) -> Option<&mut PrReport> {
+        return self.prs.iter_mut().find(|pr| pr.number == number);
+    }
+
+    /// Whether the run got through without an error stopping it.
+    pub fn succeeded(&self) -> bool {
+        return self.errors.is_empty();
+    }
+
+    /// The most recently merged PR something was decided about.
+    pub fn last_pr(&self) -> Option<u64> {
+        return self.prs
+            .iter()
+            .filter(|pr| pr.decision != Decision::Pending)
+            .max_by_key(|pr| pr.merged_at)
+            .map(|pr| pr.number);
+    }
+
+    /// The number of PRs something was decided about.
+    pub fn count(&self, decision: Decision) -> usize {
+        return self.prs.iter().filter(|pr| pr.decision == decision).count();
+    }
+
+    /// Links to the mirror PRs and failure issues made this run.
+    pub fn links(&self) -> Vec<String> {
+        return self.prs
+            .iter()
+            .filter_map(|pr| pr.pr_url.as_ref().or(pr.issue_url.as_ref()))
+            .cloned()
+            .collect();
+    }
+
+    /// Records an error that stopped the whole run.
+    pub fn error(&mut self, context: &str, error: Error) {
+        self.errors.push(format!("{}: {}", context, error));
+        if self.fatal.is_none() {
+            self.fatal = Some(error);
+        }
+    }
+
+    pub fn take_fatal(&mut self) -> Option<Error> {
+        return self.fatal.take();
+    }
+
+    /// Writes the report to `report_dir`, if it's set.
+    pub fn write(&mut self, config: &AppConfig) {
+        let directory = match &config.report_dir {
+            Some(d) => Path::new(d),
+            None => return,
+        };
+
+        self.finished_at = Some(Utc::now());
+
+        if let Err(e) = fs::create_dir_all(directory) {
+            error!("Failed to create report directory {}: {}", directory.display(), e);
+            return;
+        }
+
+        let name = format!("run_{}", self.started_at.format("%Y-%m-%dT%H-%M-%SZ"));
+
+        let json = match serde_json::to_string_pretty(self) {
+            Ok(j) => j,
+            Err(e) => {
+                error!("Failed to serialize run report: {}", e);
+                return;
+            }
+        };
+
+        for (extension, contents) in [("json", json), ("md", self.to_markdown())] {
+            let path = directory.join(format!("{}.{}", name, extension));
+            match fs::write(&path, contents) {
+                Ok(_) => info!("Wrote run report to {}.", path.display()),
+                Err(e) => error!("Failed to write run report to {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut md = String::new();
+
+        let _ = writeln!(md, "# Mirror run {}\n", self.started_at.to_rfc3339());
+        let _ = writeln!(md, "Mirroring `{}` into `{}`, for PRs merged between {} and {}.\n",
+            self.clone_repo, self.into_repo, self.window_from.to_rfc3339(), self.window_to.to_rfc3339());
+
+        if let Some(finished) = self.finished_at {
+            let _ = writeln!(md, "Finished at {}, taking {}s.", finished.to_rfc3339(), (finished - self.started_at).num_seconds());
+        }
+        if let Some(seconds) = self.gather_seconds {
+            let _ = writeln!(md, "Gathering PRs took {}.", seconds_cell(Some(seconds)));
+        }
+        if let Some(seconds) = self.fetch_seconds {
+            let _ = writeln!(md, "Fetching upstream commits took {}.", seconds_cell(Some(seconds)));
+        }
+
+        if !self.repairs.is_empty() {
+            let _ = writeln!(md, "\n## Repairs to the local repo\n");
+            for repair in &self.repairs {
+                let _ = writeln!(md, "- {}", repair);
+            }
+        }
+
+        for (decision, heading) in [(Decision::Mirrored, "Mirrored"), (Decision::Failed, "Failed"), (Decision::Pending, "Not reached"), (Decision::Filtered, "Filtered out")] {
+            let prs: Vec<&PrReport> = self.prs.iter().filter(|pr| pr.decision == decision).collect();
+            if prs.is_empty() {
+                continue;
+            }
+
+            let _ = writeln!(md, "\n## {} ({})\n", heading, prs.len());
+
+            if decision == Decision::Filtered {
+                let _ = writeln!(md, "| PR | Title | Reason |\n| --- | --- | --- |");
+                for pr in prs {
+                    let _ = writeln!(md, "| #{} | {} | {} |", pr.number, escape(&pr.title), pr.reason.as_deref().unwrap_or_default());
+                }
+                continue;
+            }
+
+            let _ = writeln!(md, "| PR | Title | Branch | Result | Fetch | Cherry-pick | Push | PR creation |\n| --- | --- | --- | --- | --- | --- | --- | --- |");
+            for pr in prs {
+                let result = match (&pr.pr_url, &pr.issue_url, &pr.error) {
+                    (Some(url), _, _) => url.clone(),
+                    (None, Some(url), Some(e)) => format!("{} ({})", url, escape(e)),
+                    (None, None, Some(e)) => escape(e),
+                    (None, Some(url), None) => url.clone(),
+                    (None, None, None) => String::new(),
+                };
+
+                let _ = writeln!(md, "| #{} | {} | {} | {} | {} | {} | {} | {} |",
+                    pr.number, escape(&pr.title), pr.branch.as_deref().unwrap_or_default(), result,
+                    seconds_cell(pr.timings.fetch), seconds_cell(pr.timings.cherry_pick),
+                    seconds_cell(pr.timings.push), seconds_cell(pr.timings.pr_creation));
+            }
+        }
+
+        if !self.errors.is_empty() {
+            let _ = writeln!(md, "\n## Errors\n");
+            for e in &self.errors {
+                let _ = writeln!(md, "- {}", e);
+            }
+        }
+
+        return md;
+    }
+}
+
+fn seconds_cell(seconds: Option<f64>) -> String {
+    return seconds.map(|s| format!("{:.2}s", s)).unwrap_or_default();
+}
+
+/// Keeps text from breaking out of its Markdown table cell.
+fn escape(text: &str) -> String {
+    return text.replace('|', "\\|").replace('\n', " ");
+}
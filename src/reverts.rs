@@ -0,0 +1,146 @@
+use crate::{mirrors::{MirrorList, MirrorPr}, AppConfig, RevertAction, NO_NET_ACTIVITY};
+use octocrab::{models::{pulls::PullRequest, Author}, params, Octocrab};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+/// What an upstream revert PR says it reverts.
+#[derive(Debug, Default)]
+struct RevertOf {
+    numbers: Vec<u64>,
+    shas: Vec<String>,
+    title: Option<String>,
+}
+
+impl RevertOf {
+    fn is_empty(&self) -> bool {
+        return self.numbers.is_empty() && self.shas.is_empty() && self.title.is_none();
+    }
+
+    fn find_mirror<'a>(&self, mirrors: &'a [MirrorPr]) -> Option<&'a MirrorPr> {
+        let by_number = mirrors.iter().find(|m| self.numbers.contains(&m.upstream_number));
+        let by_sha = || mirrors.iter().find(|m| !m.upstream_sha.is_empty()
+            && self.shas.iter().any(|s| m.upstream_sha.starts_with(s.as_str()) || s.starts_with(m.upstream_sha.as_str())));
+        let by_title = || mirrors.iter().find(|m| self.title.as_ref().is_some_and(|t| *t == m.upstream_title));
+
+        return by_number.or_else(by_sha).or_else(by_title);
+    }
+}
+
+/// Finds upstream PRs that revert something we already mirrored, and handles them according to `revert_action`.
+///
+/// Revert PRs whose original mirror got closed are removed from `prs`.
+/// Returns a map of upstream revert PR numbers to the mirror PR they revert, for cross-linking.
+pub async fn handle_reverts(octocrab: &Octocrab, config: &AppConfig, bot_info: &Author, prs: &mut Vec<PullRequest>, mirrors: &MirrorList) -> HashMap<u64, u64> {
+    let mut reverted = HashMap::new();
+
+    let reverts: Vec<(u64, RevertOf)> = prs
+        .iter()
+        .map(|pr| (pr.number, parse_revert(pr, config)))
+        .filter(|(_, r)| !r.is_empty())
+        .collect();
+
+    if reverts.is_empty() {
+        return reverted;
+    }
+
+    // Mirrors can only be looked up on GitHub, elsewhere reverts are mirrored like any other PR.
+    if !config.target_on_github() {
+        return reverted;
+    }
+
+    info!("Found {} upstream reverts, checking them against existing mirrors.", reverts.len());
+
+    let mirrors = match mirrors.get(octocrab, config, bot_info).await {
+        Ok(m) => m,
+        Err(e) => {
+            warn!("Failed to get existing mirror PRs, reverts won't be linked to their mirrors: {}", e);
+            return reverted;
+        }
+    };
+
+    let action = config.revert_action.unwrap_or_default();
+
+    for (number, revert) in reverts {
+        let mirror = match revert.find_mirror(mirrors) {
+            Some(m) => m,
+            None => continue,
+        };
+
+        info!("Upstream PR #{} reverts #{}, which was mirrored as #{}.", number, mirror.upstream_number, mirror.pr.number);
+
+        if action == RevertAction::Close && mirror.is_open() {
+            close_mirror(octocrab, config, mirror, number).await;
+            prs.retain(|pr| pr.number != number);
+            continue;
+        }
+
+        reverted.insert(number, mirror.pr.number);
+    }
+
+    return reverted;
+}
+
+/// Closes a still open mirror PR whose upstream PR got reverted, explaining why.
+async fn close_mirror(octocrab: &Octocrab, config: &AppConfig, mirror: &MirrorPr, revert_number: u64) {
+    info!("Closing mirror PR #{} since upstream reverted it.", mirror.pr.number);
+
+    if NO_NET_ACTIVITY {
+        return;
+    }
+
+    let comment = format!("Upstream reverted {}/{}#{} in {}/{}#{}, so this mirror is no longer needed and is being closed.",
+        config.clone_repo.owner, config.clone_repo.name, mirror.upstream_number,
+        config.clone_repo.owner, config.clone_repo.name, revert_number);
+
+    let _ = octocrab
+        .issues(&config.into_repo.owner, &config.into_repo.name)
+        .create_comment(mirror.pr.number, comment)
+        .await
+        .inspect_err(|e| warn!("Failed to comment on mirror PR #{}: {}", mirror.pr.number, e));
+
+    let _ = octocrab
+        .pulls(&config.into_repo.owner, &config.into_repo.name)
+        .update(mirror.pr.number)
+        .state(params::pulls::State::Closed)
+        .send()
+        .await
+        .inspect_err(|e| error!("Failed to close mirror PR #{}: {}", mirror.pr.number, e));
+}
+
+/// Reads revert markers out of an upstream PR's title and body.
+///
+/// Understands GitHub's revert button (`Revert "<title>"` and `Reverts owner/repo#N`), and git's `This reverts commit <sha>`.
+fn parse_revert(pr: &PullRequest, config: &AppConfig) -> RevertOf {
+    let mut revert = RevertOf::default();
+
+    let title = pr.title.clone().unwrap_or_default();
+    if let Some(t) = title.strip_prefix("Revert \"").and_then(|t| t.strip_suffix('"')) {
+        revert.title = Some(t.to_string());
+    }
+
+    let body = pr.body.clone().unwrap_or_default();
+    let repo_prefix = format!("{}/{}", config.clone_repo.owner, config.clone_repo.name).to_lowercase();
+
+    for line in body.lines() {
+        if let Some(rest) = line.split("This reverts commit ").nth(1) {
+            let sha: String = rest.chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+            if sha.len() >= 7 {
+                revert.shas.push(sha);
+            }
+        }
+
+        if let Some(rest) = line.trim().strip_prefix("Reverts ") {
+            let (repo, number) = rest.split_once('#').unwrap_or_default();
+            if !repo.is_empty() && repo.to_lowercase() != repo_prefix {
+                continue;
+            }
+
+            let number: String = number.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if let Ok(n) = number.parse() {
+                revert.numbers.push(n);
+            }
+        }
+    }
+
+    return revert;
+}
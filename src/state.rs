@@ -0,0 +1,92 @@
+use crate::{AppConfig, Error};
+use chrono::{DateTime, Utc};
+use std::{cell::Cell, fs, path::Path};
+use tracing::{info, warn};
+
+const DEFAULT_FILE_NAME: &str = "simple_mirror_state.json";
+
+/// What the bot remembers between runs. This lives in its own file so the config file is never written to.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Default, Clone)]
+pub struct State {
+    /// Only PRs merged after this are looked at. Until a run finishes, `date_from` is used instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<DateTime<Utc>>,
+    /// When the last run that looked at everything finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<DateTime<Utc>>,
+    /// The last upstream PR a finished run looked at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_pr: Option<u64>,
+    /// The head of a plain git `clone_repo` when a finished run gathered it, only commits after it are looked at.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<String>,
+    #[serde(default)]
+    pub assignee_rotation: Cell<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commands_checked_at: Option<DateTime<Utc>>,
+    /// Upstream PRs skipped with '/skip', on top of the config's `skipped_prs`.
+    #[serde(default)]
+    pub skipped_prs: Vec<u64>,
+}
+
+impl State {
+    /// Reads the state file, or starts from what older versions of the bot kept in the config file if there isn't one yet.
+    pub fn load(config: &AppConfig) -> Result<State, Error> {
+        let path = file_name(config);
+        if !Path::new(&path).exists() {
+            return Ok(migrate(config));
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::Config(format!("State file {} exists, but could not be read: {}", path, e))),
+        };
+
+        return serde_json::from_str(&contents)
+            .map_err(|e| Error::Config(format!("State file {} is invalid, fix or delete it to start over from 'date_from': {}", path, e)));
+    }
+
+    /// Writes the state file, through a temporary file so a crash can't leave it half written.
+    pub fn save(&self, config: &AppConfig) -> Result<(), Error> {
+        let path = file_name(config);
+        let contents = match serde_json::to_string_pretty(self) {
+            Ok(c) => c,
+            Err(e) => return Err(Error::General(format!("Failed to serialize state: {}", e))),
+        };
+
+        if config.no_write.unwrap_or(false) {
+            info!("No-write flag is set, not writing {}.", path);
+            info!("Contents would have been:\n{}", contents);
+            return Ok(());
+        }
+
+        info!("Updating {}.", path);
+
+        let temp_path = format!("{}.tmp", path);
+        return fs::write(&temp_path, contents)
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .map_err(|e| Error::Config(format!("State file {} could not be written to: {}\nAre we missing permissions?", path, e)));
+    }
+}
+
+fn file_name(config: &AppConfig) -> String {
+    return config.state_file.clone().unwrap_or(DEFAULT_FILE_NAME.to_string());
+}
+
+/// Older versions of the bot moved `date_from` and `time_offset` forward and kept their other state in the config file.
+fn migrate(config: &AppConfig) -> State {
+    let legacy = config.time_offset.is_some() || config.assignee_rotation.get() != 0 || config.commands_checked_at.is_some();
+    if !legacy {
+        return State::default();
+    }
+
+    warn!("Moving 'time_offset', 'assignee_rotation' and 'commands_checked_at' out of the config file and into {}. \
+        They can be removed from the config file once it has been written.", file_name(config));
+
+    return State {
+        cursor: Some(config.date_from_with_time().and_utc()),
+        assignee_rotation: config.assignee_rotation.clone(),
+        commands_checked_at: config.commands_checked_at,
+        ..State::default()
+    };
+}
@@ -0,0 +1,68 @@
+use super::*;
+use crate::commands::handle_commands;
+
+/// A `/retry` from a maintainer on issue #7, which has a failure issue's title and was opened by `opened_by`.
+async fn mount_retry_on_issue(env: &TestEnv, opened_by: &str) {
+    let mut failure_issue = issue(7, "Failed to cherry-pick PR #5: Add a feature");
+    failure_issue["user"] = author(opened_by);
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/issues/7", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(failure_issue))
+        .mount(&env.server)
+        .await;
+
+    let issue_url = format!("https://api.github.com/repos/{}/{}/issues/7", TARGET_OWNER, TARGET_NAME);
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/issues/comments", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "id": 1,
+            "node_id": "IC_1",
+            "url": format!("{}/comments/1", issue_url),
+            "html_url": format!("https://github.com/{}/{}/issues/7#issuecomment-1", TARGET_OWNER, TARGET_NAME),
+            "issue_url": issue_url,
+            "body": "/retry",
+            "author_association": "MEMBER",
+            "user": author("maintainer"),
+            "created_at": Utc::now(),
+            "updated_at": Utc::now(),
+        }])))
+        .mount(&env.server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/collaborators/maintainer/permission", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "permission": "read" })))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/issues/7/comments", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+        .mount(&env.server)
+        .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn answers_commands_on_failure_issues_the_bot_opened() {
+    let mut env = TestEnv::new().await;
+    env.config.state.commands_checked_at = Some(Utc::now() - Duration::hours(1));
+    mount_retry_on_issue(&env, BOT_LOGIN).await;
+
+    let (octocrab, bot_info) = (env.octocrab(), env.bot_info());
+    handle_commands(&octocrab, &mut env.config, &bot_info).await;
+
+    let replies = env.requests_to("POST", &format!("/repos/{}/{}/issues/7/comments", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0]["body"].as_str().unwrap().contains("only users with write access"), "{}", replies[0]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ignores_issues_that_only_look_like_failure_issues() {
+    let mut env = TestEnv::new().await;
+    env.config.state.commands_checked_at = Some(Utc::now() - Duration::hours(1));
+    mount_retry_on_issue(&env, "someone-else").await;
+
+    let (octocrab, bot_info) = (env.octocrab(), env.bot_info());
+    handle_commands(&octocrab, &mut env.config, &bot_info).await;
+
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/issues/7/comments", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+}
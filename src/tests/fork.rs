@@ -0,0 +1,85 @@
+use super::*;
+use crate::{mirror_prs, mirrors::MirrorList, reconcile::reconcile, report::Decision, PushMode};
+
+fn branch_head(repo: &Repository, branch: &str) -> Option<Oid> {
+    return repo.refname_to_id(&format!("refs/heads/{}", branch)).ok();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn syncs_the_fork_with_the_target() {
+    let env = TestEnv::new().await;
+    let target = Repository::open_bare(env.git_path(TARGET_OWNER, TARGET_NAME)).unwrap();
+    commit_file(&target, "target.txt", Some("Only on the target.\n"), "Change the target");
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(branch_head(&env.fork(), BRANCH), branch_head(&target, BRANCH));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deletes_branches_of_merged_and_closed_mirrors() {
+    let env = TestEnv::new().await;
+    let fork = env.fork();
+    let head = fork.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+    for branch in ["closed-mirror", "open-mirror", "reused-mirror"] {
+        fork.branch(branch, &head, false).unwrap();
+    }
+
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .and(query_param("state", "all"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            mirror_pr(&env, 10, 1, "closed-mirror", "closed"),
+            mirror_pr(&env, 11, 2, "open-mirror", "open"),
+            // Re-mirrored the same day, so the new mirror PR has the same branch as the closed one.
+            mirror_pr(&env, 12, 3, "reused-mirror", "closed"),
+            mirror_pr(&env, 13, 3, "reused-mirror", "open"),
+            mirror_pr(&env, 14, 4, "already-gone", "closed"),
+        ])))
+        .mount(&env.server)
+        .await;
+    let mirrored = env.merge_upstream_pr(5, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    env.mount_pr_creation(100).await;
+
+    let mirror_list = MirrorList::default();
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &mirror_list).await;
+    reconcile(&env.octocrab(), &env.config, &env.bot_info(), &mirror_list).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    let listings = env.server.received_requests().await.unwrap_or_default().into_iter()
+        .filter(|r| r.url.path() == format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME) && r.url.query().is_some_and(|q| q.contains("state=all")))
+        .count();
+    assert_eq!(listings, 1);
+    assert_eq!(branch_head(&fork, "closed-mirror"), None);
+    assert!(branch_head(&fork, "open-mirror").is_some());
+    assert!(branch_head(&fork, "reused-mirror").is_some());
+    assert!(branch_head(&fork, BRANCH).is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pushes_mirror_branches_into_the_target_repo() {
+    let mut env = TestEnv::new().await;
+    env.config.push_mode = Some(PushMode::SameRepo);
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    let created = env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(created.len(), 1);
+    let head = created[0]["head"].as_str().unwrap();
+    assert!(head.starts_with("mirror/"), "{}", head);
+
+    let target = Repository::open_bare(env.git_path(TARGET_OWNER, TARGET_NAME)).unwrap();
+    assert_eq!(file_on_branch(&target, head, "feature.txt").as_deref(), Some("A new feature.\n"));
+    assert_eq!(branch_head(&env.fork(), head), None);
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/forks", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+}
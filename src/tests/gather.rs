@@ -0,0 +1,118 @@
+use super::*;
+use crate::{error::Class, get_all_prs, mirror_prs, mirrors::MirrorList};
+
+fn prs(numbers: &[u64], base_uri: &str) -> Vec<Value> {
+    return numbers.iter().map(|n| pull_request(base_uri, *n, Some(recently()), "abc123", "upstream-dev")).collect();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gathers_every_page() {
+    let env = TestEnv::new().await;
+    let uri = env.server.uri();
+    env.mount_pr_pages(vec![prs(&[6, 5], &uri), prs(&[4, 3], &uri), prs(&[2, 1], &uri)]).await;
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    let numbers: Vec<u64> = all_prs.iter().map(|pr| pr.number).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4, 5, 6]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drops_duplicates_across_pages() {
+    let env = TestEnv::new().await;
+    let uri = env.server.uri();
+    // A PR opened while paging shifts everything along by one.
+    env.mount_pr_pages(vec![prs(&[4, 3], &uri), prs(&[3, 2], &uri), prs(&[1], &uri)]).await;
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    let numbers: Vec<u64> = all_prs.iter().map(|pr| pr.number).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fails_when_a_later_page_fails() {
+    let env = TestEnv::new().await;
+    let uri = env.server.uri();
+    env.mount_pr_pages(vec![prs(&[6, 5], &uri), prs(&[4, 3], &uri), prs(&[2, 1], &uri)]).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .and(query_param("page", "2"))
+        .respond_with(github_error(502, "Server Error"))
+        .with_priority(1)
+        .mount(&env.server)
+        .await;
+
+    let error = get_all_prs(&env.octocrab(), &env.config).await.unwrap_err();
+
+    assert_eq!(error.class(), Class::Transient);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_failed_page_fails_the_run() {
+    let env = TestEnv::new().await;
+    let mirrored = env.merge_upstream_pr(6, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored], prs(&[4, 3], &env.server.uri())]).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .and(query_param("page", "2"))
+        .respond_with(github_error(502, "Server Error"))
+        .with_priority(1)
+        .mount(&env.server)
+        .await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(!report.succeeded());
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rate_limits_are_transient() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .respond_with(github_error(403, "API rate limit exceeded for user ID 1.").insert_header("x-ratelimit-remaining", "0"))
+        .mount(&env.server)
+        .await;
+
+    let error = get_all_prs(&env.octocrab(), &env.config).await.unwrap_err();
+
+    assert_eq!(error.class(), Class::Transient);
+    assert_eq!(error.exit_code(), 4);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bad_credentials_are_permission_errors() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .respond_with(github_error(401, "Bad credentials"))
+        .mount(&env.server)
+        .await;
+
+    let error = get_all_prs(&env.octocrab(), &env.config).await.unwrap_err();
+
+    assert_eq!(error.class(), Class::Permission);
+    assert_eq!(error.exit_code(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reads_upstream_from_its_own_host() {
+    let mut env = TestEnv::new().await;
+    let enterprise = MockServer::start().await;
+    env.config.clone_repo.api_url = Some(enterprise.uri());
+    env.config.clone_token = Some("enterprise-token".to_string());
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .and(header("authorization", "Bearer enterprise-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(prs(&[2, 1], &enterprise.uri())))
+        .mount(&enterprise)
+        .await;
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    assert_eq!(all_prs.len(), 2);
+    assert!(env.requests_to("GET", &format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)).await.is_empty());
+}
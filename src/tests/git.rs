@@ -0,0 +1,136 @@
+use super::*;
+use crate::{get_all_prs, mirror_prs, mirrors::MirrorList, report::Decision, CommitGrouping};
+use octocrab::models::pulls::PullRequest;
+
+/// Reads upstream as a plain git repo, starting after what's on it now.
+fn plain_git(env: &mut TestEnv, grouping: CommitGrouping) {
+    env.config.clone_repo.forge = ForgeKind::Git;
+    env.config.commit_grouping = Some(grouping);
+    let head = env.upstream().refname_to_id(&format!("refs/heads/{}", BRANCH)).unwrap();
+    env.config.state.last_commit = Some(head.to_string());
+}
+
+/// Commits `file` on a side branch off upstream's head, then merges it back in after another commit on the branch.
+fn merge_side_branch(repo: &Repository, file: &str) -> Oid {
+    let signature = Signature::now("Upstream Dev", "dev@example.com").unwrap();
+    let base = repo.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+
+    let mut tree = repo.treebuilder(Some(&base.tree().unwrap())).unwrap();
+    tree.insert(file, repo.blob(b"From the side.\n").unwrap(), 0o100644).unwrap();
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let side = repo.commit(None, &signature, &signature, "Side change", &tree, &[&base]).unwrap();
+    let side = repo.find_commit(side).unwrap();
+
+    let main = repo.find_commit(commit_file(repo, "main.txt", Some("On the branch.\n"), "Branch change")).unwrap();
+
+    let merged = repo.merge_commits(&main, &side, None).unwrap().write_tree_to(repo).unwrap();
+    let merged = repo.find_tree(merged).unwrap();
+    return repo.commit(Some(&format!("refs/heads/{}", BRANCH)), &signature, &signature, "Merge side branch", &merged, &[&main, &side]).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_commits_from_a_plain_git_branch() {
+    let mut env = TestEnv::new().await;
+    plain_git(&mut env, CommitGrouping::Commit);
+    commit_file(&env.upstream(), "feature.txt", Some("A new feature.\n"), "Add a feature\n\nIt does things.");
+    commit_file(&env.upstream(), "other.txt", Some("Another one.\n"), "Add another feature");
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 2);
+
+    // The two commits already there are #1 and #2.
+    let created = env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await;
+    let titles: Vec<&str> = created.iter().map(|pr| pr["title"].as_str().unwrap()).collect();
+    assert_eq!(titles, vec!["Mirror 3: Add a feature", "Mirror 4: Add another feature"]);
+    assert!(created[0]["body"].as_str().unwrap().contains("> It does things."));
+    assert!(created[0]["body"].as_str().unwrap().contains("Upstream Dev"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn leaves_out_merges_going_commit_by_commit() {
+    let mut env = TestEnv::new().await;
+    plain_git(&mut env, CommitGrouping::Commit);
+    merge_side_branch(&env.upstream(), "side.txt");
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    let mut titles: Vec<String> = all_prs.iter().map(|pr| pr.title.clone().unwrap()).collect();
+    titles.sort();
+    assert_eq!(titles, vec!["Branch change", "Side change"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_first_parent_merges_whole() {
+    let mut env = TestEnv::new().await;
+    plain_git(&mut env, CommitGrouping::Merge);
+    let merge = merge_side_branch(&env.upstream(), "side.txt");
+    env.mount_pr_creation(100).await;
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+    let numbers: Vec<u64> = all_prs.iter().map(|pr| pr.number).collect();
+    assert_eq!(numbers, vec![3, 4]);
+    assert_eq!(all_prs[1].merge_commit_sha.as_deref(), Some(merge.to_string().as_str()));
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 2);
+    let branch = format!("{}_{}_4_{}", UPSTREAM_OWNER, UPSTREAM_NAME, Utc::now().date_naive());
+    assert_eq!(file_on_branch(&env.fork(), &branch, "side.txt").as_deref(), Some("From the side.\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_commits_dated_before_the_last_run() {
+    let mut env = TestEnv::new().await;
+    plain_git(&mut env, CommitGrouping::Commit);
+
+    // Made long ago, but only pushed since the last run.
+    let upstream = env.upstream();
+    let parent = upstream.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+    let mut tree = upstream.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+    tree.insert("old.txt", upstream.blob(b"Written long ago.\n").unwrap(), 0o100644).unwrap();
+    let tree = upstream.find_tree(tree.write().unwrap()).unwrap();
+    let signature = Signature::new("Upstream Dev", "dev@example.com", &git2::Time::new(long_ago().timestamp(), 0)).unwrap();
+    upstream.commit(Some(&format!("refs/heads/{}", BRANCH)), &signature, &signature, "An old change", &tree, &[&parent]).unwrap();
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    assert_eq!(report.count(Decision::Filtered), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merging_a_side_branch_keeps_earlier_numbers() {
+    let mut env = TestEnv::new().await;
+    plain_git(&mut env, CommitGrouping::Commit);
+    let upstream = env.upstream();
+    let signature = Signature::now("Upstream Dev", "dev@example.com").unwrap();
+    let base = upstream.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+
+    // Branched off before the change below, but only merged after it was numbered.
+    let mut tree = upstream.treebuilder(Some(&base.tree().unwrap())).unwrap();
+    tree.insert("side.txt", upstream.blob(b"From the side.\n").unwrap(), 0o100644).unwrap();
+    let tree = upstream.find_tree(tree.write().unwrap()).unwrap();
+    let side = upstream.find_commit(upstream.commit(None, &signature, &signature, "Side change", &tree, &[&base]).unwrap()).unwrap();
+    let main = upstream.find_commit(commit_file(&upstream, "main.txt", Some("On the branch.\n"), "Branch change")).unwrap();
+
+    let numbered = |prs: Vec<PullRequest>| -> Vec<(u64, String)> {
+        let mut numbered: Vec<(u64, String)> = prs.into_iter().map(|pr| (pr.number, pr.title.unwrap())).collect();
+        numbered.sort();
+        return numbered;
+    };
+    let before = numbered(get_all_prs(&env.octocrab(), &env.config).await.unwrap());
+    assert_eq!(before, vec![(3, "Branch change".to_string())]);
+
+    let merged = upstream.merge_commits(&main, &side, None).unwrap().write_tree_to(&upstream).unwrap();
+    let merged = upstream.find_tree(merged).unwrap();
+    upstream.commit(Some(&format!("refs/heads/{}", BRANCH)), &signature, &signature, "Merge side branch", &merged, &[&main, &side]).unwrap();
+
+    let after = numbered(get_all_prs(&env.octocrab(), &env.config).await.unwrap());
+    assert_eq!(after, vec![(3, "Branch change".to_string()), (4, "Side change".to_string())]);
+}
@@ -0,0 +1,123 @@
+use super::*;
+use crate::{get_all_prs, make_issue, mirror_prs, mirrors::MirrorList, report::Decision, error::Error};
+
+/// A PR as Gitea lists it, which is shaped differently from GitHub's.
+fn gitea_pull_request(host: &str, number: u64, merged_at: Option<DateTime<Utc>>, base: &str) -> Value {
+    let owner = json!({ "id": 1, "login": UPSTREAM_OWNER, "email": "", "avatar_url": format!("{}/avatars/1", host), "is_admin": false });
+    let repo = json!({
+        "id": 1,
+        "name": UPSTREAM_NAME,
+        "full_name": format!("{}/{}", UPSTREAM_OWNER, UPSTREAM_NAME),
+        "owner": owner,
+        "html_url": format!("{}/{}/{}", host, UPSTREAM_OWNER, UPSTREAM_NAME),
+        "clone_url": format!("{}/{}/{}.git", host, UPSTREAM_OWNER, UPSTREAM_NAME),
+    });
+    let html_url = format!("{}/{}/{}/pulls/{}", host, UPSTREAM_OWNER, UPSTREAM_NAME, number);
+
+    return json!({
+        "id": 1000 + number,
+        "url": html_url,
+        "number": number,
+        "user": { "id": 7, "login": "upstream-dev", "email": "dev@example.com", "avatar_url": format!("{}/avatars/7", host), "is_admin": false },
+        "title": format!("Change #{}", number),
+        "body": format!("This is change #{}.", number),
+        "labels": [{ "id": 3, "name": "Bug", "color": "ee0701", "description": "", "url": format!("{}/api/v1/repos/{}/{}/labels/3", host, UPSTREAM_OWNER, UPSTREAM_NAME) }],
+        "state": "closed",
+        "html_url": html_url,
+        "diff_url": format!("{}.diff", html_url),
+        "patch_url": format!("{}.patch", html_url),
+        "merged": merged_at.is_some(),
+        "merged_at": merged_at,
+        "merge_commit_sha": merged_at.map(|_| "abc123"),
+        "base": { "label": base, "ref": base, "sha": "def456", "repo_id": 1, "repo": repo },
+        "head": { "label": "feature", "ref": "feature", "sha": "abc123", "repo_id": 1, "repo": repo },
+        "created_at": "2024-01-01T12:00:00+01:00",
+        "updated_at": "2024-01-02T12:00:00+01:00",
+        "closed_at": merged_at,
+    });
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gathers_prs_from_gitea() {
+    let mut env = TestEnv::new().await;
+    let gitea = MockServer::start().await;
+    let host = gitea.uri();
+    env.config.clone_repo.forge = ForgeKind::Gitea;
+    env.config.clone_repo.api_url = Some(format!("{}/api/v1", host));
+    env.config.clone_token = Some("gitea-token".to_string());
+
+    let list_path = format!("/api/v1/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME);
+    let pages = [
+        vec![gitea_pull_request(&host, 3, Some(recently()), BRANCH), gitea_pull_request(&host, 2, Some(recently()), "release")],
+        vec![gitea_pull_request(&host, 1, None, BRANCH)],
+    ];
+    for (index, prs) in pages.iter().enumerate() {
+        Mock::given(method("GET"))
+            .and(path(list_path.as_str()))
+            .and(query_param("page", (index + 1).to_string()))
+            .and(header("authorization", "token gitea-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(prs).insert_header("x-total-count", "51"))
+            .mount(&gitea)
+            .await;
+    }
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    // #2 was into another branch.
+    let numbers: Vec<u64> = all_prs.iter().map(|pr| pr.number).collect();
+    assert_eq!(numbers, vec![1, 3]);
+
+    let merged = &all_prs[1];
+    assert!(merged.merged_at.is_some());
+    assert_eq!(merged.merge_commit_sha.as_deref(), Some("abc123"));
+    assert_eq!(merged.user.as_ref().unwrap().login, "upstream-dev");
+    assert_eq!(merged.labels.as_ref().unwrap()[0].name, "Bug");
+    assert_eq!(merged.base.repo.as_ref().unwrap().owner.as_ref().unwrap().login, UPSTREAM_OWNER);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_into_gitea() {
+    let mut env = TestEnv::new().await;
+    env.config.into_repo.forge = ForgeKind::Gitea;
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    env.mount_pr_creation(100).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/labels", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 11, "name": "Mirror", "color": "00aabb" }])))
+        .mount(&env.server)
+        .await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+
+    let created = env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(created.len(), 1);
+    assert!(created[0]["title"].as_str().unwrap().starts_with("WIP: "));
+    assert_eq!(created[0]["head"], format!("{}:{}_{}_2_{}", BOT_LOGIN, UPSTREAM_OWNER, UPSTREAM_NAME, Utc::now().date_naive()));
+
+    // Gitea adds labels by ID.
+    let labeled = env.requests_to("POST", &format!("/repos/{}/{}/issues/100/labels", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(labeled[0]["labels"], json!([11]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn opens_issues_on_gitea_without_missing_labels() {
+    let mut env = TestEnv::new().await;
+    env.config.into_repo.forge = ForgeKind::Gitea;
+    env.mount_issue_creation(202).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/labels", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&env.server)
+        .await;
+    let upstream = serde_json::from_value(pull_request(&env.server.uri(), 9, Some(recently()), "abc123", "upstream-dev")).unwrap();
+
+    let url = make_issue(&env.config, &env.octocrab(), upstream, Error::General("Something broke".to_string())).await;
+
+    assert_eq!(url.as_deref(), Some("https://github.com/fork-org/game-fork/issues/202"));
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues[0]["labels"], json!([]));
+}
@@ -0,0 +1,151 @@
+use super::*;
+use crate::{get_all_prs, mirror_prs, mirrors::MirrorList, report::Decision};
+
+/// A merge request as GitLab lists it, which leaves out `diff_refs` and the commit count.
+fn gitlab_merge_request(host: &str, iid: u64, merged_at: Option<DateTime<Utc>>) -> Value {
+    let web_url = format!("{}/{}/{}/-/merge_requests/{}", host, UPSTREAM_OWNER, UPSTREAM_NAME, iid);
+
+    return json!({
+        "id": 2000 + iid,
+        "iid": iid,
+        "project_id": 1,
+        "target_project_id": 1,
+        "title": format!("Change #{}", iid),
+        "description": format!("This is change #{}.", iid),
+        "state": if merged_at.is_some() { "merged" } else { "closed" },
+        "author": { "id": 7, "username": "upstream-dev", "name": "Upstream Dev", "avatar_url": null, "web_url": format!("{}/upstream-dev", host) },
+        "labels": ["Bug"],
+        "draft": false,
+        "source_branch": "feature",
+        "target_branch": BRANCH,
+        "sha": "abc123",
+        "merge_commit_sha": merged_at.map(|_| "fed987"),
+        "squash_commit_sha": null,
+        "web_url": web_url,
+        "created_at": "2024-01-01T12:00:00+01:00",
+        "updated_at": "2024-01-02T12:00:00+01:00",
+        "merged_at": merged_at,
+        "closed_at": null,
+    });
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn gathers_merge_requests_from_gitlab() {
+    let mut env = TestEnv::new().await;
+    let gitlab = MockServer::start().await;
+    let host = gitlab.uri();
+    env.config.clone_repo.forge = ForgeKind::Gitlab;
+    env.config.clone_repo.api_url = Some(format!("{}/api/v4", host));
+    env.config.clone_token = Some("gitlab-token".to_string());
+
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v4/projects/{}%2F{}/merge_requests", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .and(query_param("state", "merged"))
+        .and(query_param("target_branch", BRANCH))
+        .and(header("private-token", "gitlab-token"))
+        .respond_with(ResponseTemplate::new(200)
+            .set_body_json(json!([gitlab_merge_request(&host, 4, Some(recently()))]))
+            .insert_header("x-total-pages", "1"))
+        .mount(&gitlab)
+        .await;
+
+    let all_prs = get_all_prs(&env.octocrab(), &env.config).await.unwrap();
+
+    assert_eq!(all_prs.len(), 1);
+    let merged = &all_prs[0];
+    assert_eq!(merged.number, 4);
+    assert_eq!(merged.merge_commit_sha.as_deref(), Some("fed987"));
+    assert_eq!(merged.user.as_ref().unwrap().login, "upstream-dev");
+    assert_eq!(merged.labels.as_ref().unwrap()[0].name, "Bug");
+    assert_eq!(merged.html_url.as_ref().unwrap().as_str(), format!("{}/{}/{}/-/merge_requests/4", host, UPSTREAM_OWNER, UPSTREAM_NAME));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_into_gitlab() {
+    let mut env = TestEnv::new().await;
+    env.config.into_repo.forge = ForgeKind::Gitlab;
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+
+    let target = format!("/projects/{}%2F{}", TARGET_OWNER, TARGET_NAME);
+    Mock::given(method("POST"))
+        .and(path(format!("{}/fork", target)))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": 3, "http_url_to_repo": env.git_url(BOT_LOGIN, TARGET_NAME) })))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(target.as_str()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 2 })))
+        .mount(&env.server)
+        .await;
+    let mut created = gitlab_merge_request(&env.server.uri(), 100, None);
+    created["state"] = json!("opened");
+    Mock::given(method("POST"))
+        .and(path(format!("/projects/{}%2F{}/merge_requests", BOT_LOGIN, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(201).set_body_json(created))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(format!("{}/merge_requests/100", target)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&env.server)
+        .await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+
+    // Opened on the bot's fork, into the target project.
+    let requests = env.requests_to("POST", &format!("/projects/{}%2F{}/merge_requests", BOT_LOGIN, TARGET_NAME)).await;
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0]["title"].as_str().unwrap().starts_with("Draft: "));
+    assert_eq!(requests[0]["target_project_id"], 2);
+    assert_eq!(requests[0]["source_branch"], format!("{}_{}_2_{}", UPSTREAM_OWNER, UPSTREAM_NAME, Utc::now().date_naive()));
+
+    let labeled = env.requests_to("PUT", &format!("{}/merge_requests/100", target)).await;
+    assert_eq!(labeled[0]["add_labels"], "Mirror");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fails_fast_forwarded_merge_requests_of_several_commits() {
+    let mut env = TestEnv::new().await;
+    let gitlab = MockServer::start().await;
+    let host = gitlab.uri();
+    env.config.clone_repo.forge = ForgeKind::Gitlab;
+    env.config.clone_repo.api_url = Some(format!("{}/api/v4", host));
+
+    commit_file(&env.upstream(), "first.txt", Some("The first half.\n"), "First half");
+    let head = commit_file(&env.upstream(), "second.txt", Some("The second half.\n"), "Second half");
+
+    // Fast-forwarded, so there's no merge commit and the head is the last of the two.
+    let mut merge_request = gitlab_merge_request(&host, 4, Some(recently()));
+    merge_request["sha"] = json!(head.to_string());
+    merge_request["merge_commit_sha"] = Value::Null;
+    let project = format!("/api/v4/projects/{}%2F{}", UPSTREAM_OWNER, UPSTREAM_NAME);
+    Mock::given(method("GET"))
+        .and(path(format!("{}/merge_requests", project)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([merge_request])).insert_header("x-total-pages", "1"))
+        .mount(&gitlab)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/merge_requests/4", project)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&merge_request))
+        .mount(&gitlab)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/merge_requests/4/commits", project)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": head.to_string() }, { "id": "0000000" }])).insert_header("x-total", "2"))
+        .mount(&gitlab)
+        .await;
+    env.mount_pr_creation(100).await;
+    env.mount_issue_creation(200).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert_eq!(report.count(Decision::Failed), 1);
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0]["body"].as_str().unwrap().contains("merged as 2 commits"), "{}", issues[0]);
+}
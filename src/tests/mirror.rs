@@ -0,0 +1,286 @@
+use super::*;
+use crate::{error::{Class, Error}, make_issue, make_pull_request, mirror_branch_name, mirror_prs, mirrors::MirrorList, report::Decision, AssigneeMode, MirrorLinks};
+use octocrab::models::pulls::PullRequest;
+
+fn to_pr(value: &Value) -> PullRequest {
+    return serde_json::from_value(value.clone()).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn mirrors_merged_prs_end_to_end() {
+    let env = TestEnv::new().await;
+    let uri = env.server.uri();
+    let old = env.merge_upstream_pr(1, "old.txt", Some("Old.\n"), long_ago());
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    let unmerged = pull_request(&uri, 3, None, "abc123", "upstream-dev");
+    let ignored = pull_request(&uri, 4, Some(recently()), "abc123", "github-actions[bot]");
+    env.mount_pr_pages(vec![vec![ignored, unmerged, mirrored.clone(), old]]).await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    assert_eq!(report.count(Decision::Filtered), 3);
+    assert_eq!(report.count(Decision::Failed), 0);
+
+    let branch = mirror_branch_name(&env.config, &to_pr(&mirrored));
+    assert_eq!(file_on_branch(&env.fork(), &branch, "feature.txt").as_deref(), Some("A new feature.\n"));
+    assert_eq!(file_on_branch(&env.fork(), &branch, "old.txt"), None);
+
+    let created = env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0]["head"], format!("{}:{}", BOT_LOGIN, branch));
+    assert_eq!(created[0]["base"], BRANCH);
+    assert_eq!(created[0]["draft"], true);
+    assert!(created[0]["title"].as_str().unwrap().contains("Change #2"));
+
+    let labeled = env.requests_to("POST", &format!("/repos/{}/{}/issues/100/labels", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(labeled.len(), 1);
+    assert_eq!(labeled[0]["labels"], json!(["Mirror"]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fetches_merge_commits_by_sha() {
+    let mut env = TestEnv::new().await;
+    env.config.fetch_depth = Some(1);
+
+    // Only reachable from the PR's own ref, so fetching the branch wouldn't find it.
+    let upstream = env.upstream();
+    let head = upstream.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+    let mut tree = upstream.treebuilder(Some(&head.tree().unwrap())).unwrap();
+    tree.insert("feature.txt", upstream.blob(b"A new feature.\n").unwrap(), 0o100644).unwrap();
+    let tree = upstream.find_tree(tree.write().unwrap()).unwrap();
+    let signature = Signature::now("Upstream Dev", "dev@example.com").unwrap();
+    let sha = upstream.commit(Some("refs/pull/7/merge"), &signature, &signature, "Change #7", &tree, &[&head]).unwrap();
+
+    let merged = pull_request(&env.server.uri(), 7, Some(recently()), &sha.to_string(), "upstream-dev");
+    env.mount_pr_pages(vec![vec![merged.clone()]]).await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    let branch = mirror_branch_name(&env.config, &to_pr(&merged));
+    assert_eq!(file_on_branch(&env.fork(), &branch, "feature.txt").as_deref(), Some("A new feature.\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fetches_through_pr_refs_and_fails_missing_commits_up_front() {
+    let env = TestEnv::new().await;
+    let uri = env.server.uri();
+
+    // Only on the PR's head ref, and since the other commit is missing, fetching by SHA fails.
+    let upstream = env.upstream();
+    let head = upstream.find_reference(&format!("refs/heads/{}", BRANCH)).unwrap().peel_to_commit().unwrap();
+    let mut tree = upstream.treebuilder(Some(&head.tree().unwrap())).unwrap();
+    tree.insert("feature.txt", upstream.blob(b"A new feature.\n").unwrap(), 0o100644).unwrap();
+    let tree = upstream.find_tree(tree.write().unwrap()).unwrap();
+    let signature = Signature::now("Upstream Dev", "dev@example.com").unwrap();
+    let sha = upstream.commit(Some("refs/pull/7/head"), &signature, &signature, "Change #7", &tree, &[&head]).unwrap();
+
+    let on_pr_ref = pull_request(&uri, 7, Some(recently()), &sha.to_string(), "upstream-dev");
+    let gone = pull_request(&uri, 8, Some(recently()), "1234567890abcdef1234567890abcdef12345678", "upstream-dev");
+    env.mount_pr_pages(vec![vec![gone, on_pr_ref]]).await;
+    env.mount_pr_creation(100).await;
+    env.mount_issue_creation(200).await;
+
+    let mut report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    assert_eq!(report.count(Decision::Failed), 1);
+    assert!(report.pr(8).unwrap().issue_url.is_some());
+
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert!(issues[0]["body"].as_str().unwrap().contains("**Stage:** fetch"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conflicts_open_an_issue() {
+    let env = TestEnv::new().await;
+    env.commit_target("notes.txt", None, "Remove notes");
+    let conflicting = env.merge_upstream_pr(5, "notes.txt", Some("Better notes.\n"), recently());
+    env.mount_pr_pages(vec![vec![conflicting]]).await;
+    env.mount_pr_creation(100).await;
+    env.mount_issue_creation(200).await;
+
+    let mut report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert_eq!(report.count(Decision::Failed), 1);
+    let pr = report.pr(5).unwrap();
+    assert_eq!(pr.issue_url.as_deref(), Some("https://github.com/fork-org/game-fork/issues/200"));
+    assert!(pr.error.as_deref().unwrap().contains("notes.txt"));
+
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues.len(), 1);
+    let body = issues[0]["body"].as_str().unwrap();
+    assert!(body.contains("**Stage:** cherry-pick, **Kind:** conflict"), "{}", body);
+    assert_eq!(issues[0]["labels"], json!(["Mirror Failed"]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refused_prs_open_an_issue() {
+    let env = TestEnv::new().await;
+    let refused = env.merge_upstream_pr(6, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![refused]]).await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(github_error(422, "Validation Failed"))
+        .mount(&env.server)
+        .await;
+    env.mount_issue_creation(201).await;
+
+    let mut report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    // A single PR failing doesn't stop the run.
+    assert!(report.succeeded());
+    assert_eq!(report.count(Decision::Failed), 1);
+    assert!(report.pr(6).unwrap().issue_url.is_some());
+
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0]["body"].as_str().unwrap().contains("**Stage:** PR creation, **Kind:** permanent"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failing_to_list_prs_stops_the_run() {
+    let env = TestEnv::new().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .respond_with(github_error(503, "Service Unavailable"))
+        .mount(&env.server)
+        .await;
+
+    let mut report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(!report.succeeded());
+    assert_eq!(report.take_fatal().unwrap().exit_code(), 4);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn make_pull_request_opens_a_draft() {
+    let env = TestEnv::new().await;
+    env.mount_pr_creation(101).await;
+    let upstream = to_pr(&pull_request(&env.server.uri(), 7, Some(recently()), "abc123", "upstream-dev"));
+
+    let pr = make_pull_request(&env.config, &env.octocrab(), &env.bot_info(), upstream, Some("abc123".to_string()), "mirror-branch", MirrorLinks::default())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(pr.number, 101);
+    let created = env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(created[0]["head"], format!("{}:mirror-branch", BOT_LOGIN));
+    assert_eq!(created[0]["maintainer_can_modify"], true);
+    assert!(created[0]["body"].as_str().unwrap().contains("abc123"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn make_pull_request_server_errors_are_transient() {
+    let env = TestEnv::new().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(github_error(502, "Server Error"))
+        .mount(&env.server)
+        .await;
+    let upstream = to_pr(&pull_request(&env.server.uri(), 8, Some(recently()), "abc123", "upstream-dev"));
+
+    let error = make_pull_request(&env.config, &env.octocrab(), &env.bot_info(), upstream, Some("abc123".to_string()), "mirror-branch", MirrorLinks::default())
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.class(), Class::Transient);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keeps_a_pr_that_was_opened_despite_an_error() {
+    let env = TestEnv::new().await;
+    let mirrored = env.merge_upstream_pr(5, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(github_error(502, "Server Error"))
+        .mount(&env.server)
+        .await;
+    let existing = mirror_pr(&env, 100, 5, "mirror", "open");
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .and(query_param("state", "open"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([existing])))
+        .mount(&env.server)
+        .await;
+
+    let mut report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert_eq!(report.count(Decision::Mirrored), 1);
+    assert_eq!(json!(report.pr(5).unwrap().pr_url), existing["html_url"]);
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn make_issue_returns_the_issue_url() {
+    let mut env = TestEnv::new().await;
+    env.config.pr_settings.assignees = vec!["maintainer".to_string()];
+    env.mount_issue_creation(202).await;
+    let upstream = to_pr(&pull_request(&env.server.uri(), 9, Some(recently()), "abc123", "upstream-dev"));
+
+    let url = make_issue(&env.config, &env.octocrab(), upstream, Error::Conflict(vec!["notes.txt".to_string()])).await;
+
+    assert_eq!(url.as_deref(), Some("https://github.com/fork-org/game-fork/issues/202"));
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues[0]["assignees"], json!(["maintainer"]));
+    assert!(issues[0]["body"].as_str().unwrap().contains("`notes.txt`"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn make_issue_survives_github_refusing() {
+    let env = TestEnv::new().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(github_error(403, "Resource not accessible by integration"))
+        .mount(&env.server)
+        .await;
+    let upstream = to_pr(&pull_request(&env.server.uri(), 10, Some(recently()), "abc123", "upstream-dev"));
+
+    let url = make_issue(&env.config, &env.octocrab(), upstream, Error::General("Something broke".to_string())).await;
+
+    assert_eq!(url, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_prs_dont_use_up_assignee_turns() {
+    let mut env = TestEnv::new().await;
+    let state_file = env.dir.path().join("state.json");
+    env.config.state_file = Some(state_file.to_string_lossy().into_owned());
+    env.config.pr_settings.assignee_mode = AssigneeMode::RoundRobin;
+    env.config.pr_settings.assignees = vec!["first".to_string(), "second".to_string()];
+    let mirrored = env.merge_upstream_pr(5, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(github_error(422, "Validation Failed"))
+        .mount(&env.server)
+        .await;
+    env.mount_issue_creation(200).await;
+
+    mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    let issues = env.requests_to("POST", &format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0]["assignees"], json!(["first"]));
+
+    let state: Value = serde_json::from_str(&std::fs::read_to_string(state_file).unwrap()).unwrap();
+    assert_eq!(state["assignee_rotation"], 1);
+}
+
+#[test]
+fn errors_outside_a_stage_keep_their_pr() {
+    let error = Error::from("No merge commit").for_pr(5, Some("abc1234"));
+
+    assert_eq!(error.stage(), None);
+    assert_eq!(error.to_string(), "mirroring failed for PR #5 (abc1234): General error: No merge commit");
+}
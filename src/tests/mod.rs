@@ -0,0 +1,364 @@
+//! End to end tests against a mock GitHub API and local bare repos standing in for the GitHub remotes.
+
+use crate::{forge::{self, ForgeKind}, AppConfig, RepoInfo, State};
+use chrono::{DateTime, Duration, Utc};
+use git2::{build::RepoBuilder, Commit, Oid, Repository, Signature};
+use octocrab::{models::Author, Octocrab};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tempfile::TempDir;
+use wiremock::{
+    matchers::{header, method, path, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+mod commands;
+mod fork;
+mod gather;
+mod git;
+mod gitea;
+mod gitlab;
+mod mirror;
+mod reconcile;
+mod repair;
+
+pub const UPSTREAM_OWNER: &str = "upstream-org";
+pub const UPSTREAM_NAME: &str = "game";
+pub const TARGET_OWNER: &str = "fork-org";
+pub const TARGET_NAME: &str = "game-fork";
+pub const BOT_LOGIN: &str = "mirror-bot";
+pub const BRANCH: &str = "master";
+
+/// A mock GitHub, with upstream, target and bot fork repos on disk, and a config pointing at all of it.
+pub struct TestEnv {
+    pub dir: TempDir,
+    pub server: MockServer,
+    pub config: AppConfig,
+}
+
+impl TestEnv {
+    /// Sets up the repos with a shared first commit, and mocks what every run needs: the bot user, forking and the rate limit.
+    pub async fn new() -> TestEnv {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+
+        let env = TestEnv {
+            config: AppConfig {
+                org_token: "org-token".to_string(),
+                bot_token: "bot-token".to_string(),
+                clone_repo: repo_info(UPSTREAM_OWNER, UPSTREAM_NAME, &server, &dir),
+                into_repo: repo_info(TARGET_OWNER, TARGET_NAME, &server, &dir),
+                days_between: 0,
+                pr_labels: vec!["Mirror".to_string()],
+                issue_labels: vec!["Mirror Failed".to_string()],
+                ignored_users: vec!["github-actions[bot]".to_string()],
+                transient_retries: Some(0),
+                repo_dir: Some(dir.path().join("repos").to_string_lossy().into_owned()),
+                state: State { cursor: Some(cutoff()), ..State::default() },
+                ..AppConfig::default()
+            },
+            dir,
+            server,
+        };
+
+        let upstream = Repository::init_bare(env.git_path(UPSTREAM_OWNER, UPSTREAM_NAME)).unwrap();
+        upstream.set_head(&format!("refs/heads/{}", BRANCH)).unwrap();
+        commit_file(&upstream, "README.md", Some("A game.\n"), "Initial commit");
+        commit_file(&upstream, "notes.txt", Some("Some notes.\n"), "Add notes");
+        env.clone_bare(UPSTREAM_OWNER, UPSTREAM_NAME, TARGET_OWNER, TARGET_NAME);
+        env.clone_bare(UPSTREAM_OWNER, UPSTREAM_NAME, BOT_LOGIN, TARGET_NAME);
+
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(author(BOT_LOGIN)))
+            .mount(&env.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/{}/{}/forks", TARGET_OWNER, TARGET_NAME)))
+            .respond_with(ResponseTemplate::new(202).set_body_json(json!({
+                "id": 3,
+                "name": TARGET_NAME,
+                "url": format!("{}/repos/{}/{}", env.server.uri(), BOT_LOGIN, TARGET_NAME),
+                "clone_url": env.git_url(BOT_LOGIN, TARGET_NAME),
+            })))
+            .mount(&env.server)
+            .await;
+
+        let rate = json!({ "limit": 5000, "used": 1, "remaining": 4999, "reset": 0 });
+        Mock::given(method("GET"))
+            .and(path("/rate_limit"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "resources": { "core": rate, "search": rate },
+                "rate": rate,
+            })))
+            .mount(&env.server)
+            .await;
+
+        return env;
+    }
+
+    pub fn git_path(&self, owner: &str, name: &str) -> PathBuf {
+        return self.dir.path().join("git").join(owner).join(name);
+    }
+
+    pub fn git_url(&self, owner: &str, name: &str) -> String {
+        return format!("file://{}", self.git_path(owner, name).display());
+    }
+
+    fn clone_bare(&self, from_owner: &str, from_name: &str, owner: &str, name: &str) {
+        RepoBuilder::new()
+            .bare(true)
+            .clone(&self.git_url(from_owner, from_name), &self.git_path(owner, name))
+            .unwrap();
+    }
+
+    pub fn upstream(&self) -> Repository {
+        return Repository::open_bare(self.git_path(UPSTREAM_OWNER, UPSTREAM_NAME)).unwrap();
+    }
+
+    pub fn fork(&self) -> Repository {
+        return Repository::open_bare(self.git_path(BOT_LOGIN, TARGET_NAME)).unwrap();
+    }
+
+    /// Commits to the target repo and the bot's fork of it, as if a change was merged there and synced to the fork.
+    pub fn commit_target(&self, file: &str, content: Option<&str>, message: &str) {
+        for owner in [TARGET_OWNER, BOT_LOGIN] {
+            let repo = Repository::open_bare(self.git_path(owner, TARGET_NAME)).unwrap();
+            commit_file(&repo, file, content, message);
+        }
+    }
+
+    /// Merges an upstream PR as a single commit changing `file`, returning the PR as GitHub would list it.
+    pub fn merge_upstream_pr(&self, number: u64, file: &str, content: Option<&str>, merged_at: DateTime<Utc>) -> Value {
+        let sha = commit_file(&self.upstream(), file, content, &format!("Change #{}", number));
+        return pull_request(&self.server.uri(), number, Some(merged_at), &sha.to_string(), "upstream-dev");
+    }
+
+    pub fn octocrab(&self) -> Octocrab {
+        return forge::github_client(&self.config.into_repo, &self.config.org_token).unwrap();
+    }
+
+    pub fn bot_info(&self) -> Author {
+        return serde_json::from_value(author(BOT_LOGIN)).unwrap();
+    }
+
+    /// Serves the upstream PR list as pages, linked together with `Link` headers the way GitHub does.
+    pub async fn mount_pr_pages(&self, pages: Vec<Vec<Value>>) {
+        let list_path = format!("/repos/{}/{}/pulls", UPSTREAM_OWNER, UPSTREAM_NAME);
+        let page_url = |page: usize| format!("{}{}?state=closed&per_page=100&page={}", self.server.uri(), list_path, page);
+
+        for (index, prs) in pages.iter().enumerate() {
+            let page = index + 1;
+            let mut links = Vec::new();
+            if page < pages.len() {
+                links.push(format!("<{}>; rel=\"next\"", page_url(page + 1)));
+                links.push(format!("<{}>; rel=\"last\"", page_url(pages.len())));
+            }
+            if page > 1 {
+                links.push(format!("<{}>; rel=\"prev\"", page_url(page - 1)));
+                links.push(format!("<{}>; rel=\"first\"", page_url(1)));
+            }
+
+            let mut response = ResponseTemplate::new(200).set_body_json(prs);
+            if !links.is_empty() {
+                response = response.insert_header("Link", links.join(", ").as_str());
+            }
+
+            // Anything that isn't asking for a later page gets the first one.
+            let mock = Mock::given(method("GET")).and(path(list_path.as_str()));
+            match page {
+                1 => mock.respond_with(response).with_priority(5).mount(&self.server).await,
+                _ => mock.and(query_param("page", page.to_string())).respond_with(response).with_priority(2).mount(&self.server).await,
+            }
+        }
+    }
+
+    /// Accepts mirror PRs, answering with PR #`number` in the target repo.
+    pub async fn mount_pr_creation(&self, number: u64) {
+        let mut pr = pull_request(&self.server.uri(), number, None, "0000000", BOT_LOGIN);
+        pr["html_url"] = json!(format!("https://github.com/{}/{}/pull/{}", TARGET_OWNER, TARGET_NAME, number));
+
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+            .respond_with(ResponseTemplate::new(201).set_body_json(pr))
+            .mount(&self.server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/{}/{}/issues/{}/labels", TARGET_OWNER, TARGET_NAME, number)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Accepts failure issues, answering with issue #`number` in the target repo.
+    pub async fn mount_issue_creation(&self, number: u64) {
+        Mock::given(method("POST"))
+            .and(path(format!("/repos/{}/{}/issues", TARGET_OWNER, TARGET_NAME)))
+            .respond_with(move |request: &Request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+                ResponseTemplate::new(201).set_body_json(issue(number, body["title"].as_str().unwrap_or_default()))
+            })
+            .mount(&self.server)
+            .await;
+    }
+
+    /// The JSON bodies of every request made to the given method and path.
+    pub async fn requests_to(&self, request_method: &str, request_path: &str) -> Vec<Value> {
+        return self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter(|r| r.method.as_str() == request_method && r.url.path() == request_path)
+            .map(|r| serde_json::from_slice(&r.body).unwrap_or_default())
+            .collect();
+    }
+}
+
+/// A repo on the mock GitHub, cloned from the bare repos under `dir`.
+pub fn repo_info(owner: &str, name: &str, server: &MockServer, dir: &TempDir) -> RepoInfo {
+    return RepoInfo {
+        owner: owner.to_string(),
+        name: name.to_string(),
+        branch: BRANCH.to_string(),
+        forge: ForgeKind::Github,
+        api_url: Some(server.uri()),
+        git_host: Some(format!("file://{}", dir.path().join("git").display())),
+    };
+}
+
+/// Commits `content` to `file` on top of the branch in a bare repo, or deletes `file` if there's no content.
+pub fn commit_file(repo: &Repository, file: &str, content: Option<&str>, message: &str) -> Oid {
+    let reference = format!("refs/heads/{}", BRANCH);
+    let parent = repo.find_reference(&reference).and_then(|r| r.peel_to_commit()).ok();
+    let parent_tree = parent.as_ref().map(|p| p.tree().unwrap());
+
+    let mut tree = repo.treebuilder(parent_tree.as_ref()).unwrap();
+    match content {
+        Some(c) => {
+            let blob = repo.blob(c.as_bytes()).unwrap();
+            tree.insert(file, blob, 0o100644).unwrap();
+        }
+        None => tree.remove(file).unwrap(),
+    }
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+
+    let signature = Signature::now("Upstream Dev", "dev@example.com").unwrap();
+    let parents: Vec<&Commit> = parent.iter().collect();
+    return repo.commit(Some(&reference), &signature, &signature, message, &tree, &parents).unwrap();
+}
+
+/// The contents of `file` at the tip of `branch` in a bare repo, if it's there.
+pub fn file_on_branch(repo: &Repository, branch: &str, file: &str) -> Option<String> {
+    let commit = repo.find_branch(branch, git2::BranchType::Local).ok()?.get().peel_to_commit().ok()?;
+    let entry = commit.tree().ok()?.get_path(std::path::Path::new(file)).ok()?;
+    let blob = repo.find_blob(entry.id()).ok()?;
+    return Some(String::from_utf8_lossy(blob.content()).into_owned());
+}
+
+/// Only PRs merged after this are mirrored.
+pub fn cutoff() -> DateTime<Utc> {
+    return Utc::now() - Duration::days(7);
+}
+
+/// After the cutoff.
+pub fn recently() -> DateTime<Utc> {
+    return Utc::now() - Duration::days(2);
+}
+
+/// Before the cutoff.
+pub fn long_ago() -> DateTime<Utc> {
+    return Utc::now() - Duration::days(60);
+}
+
+pub fn author(login: &str) -> Value {
+    let url = format!("https://api.github.com/users/{}", login);
+    return json!({
+        "login": login,
+        "id": 1,
+        "node_id": "MDQ6VXNlcjE=",
+        "avatar_url": "https://avatars.githubusercontent.com/u/1",
+        "gravatar_id": "",
+        "url": url,
+        "html_url": format!("https://github.com/{}", login),
+        "followers_url": format!("{}/followers", url),
+        "following_url": format!("{}/following", url),
+        "gists_url": format!("{}/gists", url),
+        "starred_url": format!("{}/starred", url),
+        "subscriptions_url": format!("{}/subscriptions", url),
+        "organizations_url": format!("{}/orgs", url),
+        "repos_url": format!("{}/repos", url),
+        "events_url": format!("{}/events", url),
+        "received_events_url": format!("{}/received_events", url),
+        "type": "User",
+        "site_admin": false,
+    });
+}
+
+/// An upstream PR as GitHub lists it, merged at `merged_at` as `sha` if it was merged.
+pub fn pull_request(base_uri: &str, number: u64, merged_at: Option<DateTime<Utc>>, sha: &str, user: &str) -> Value {
+    let html_url = format!("https://github.com/{}/{}/pull/{}", UPSTREAM_OWNER, UPSTREAM_NAME, number);
+    return json!({
+        "url": format!("{}/repos/{}/{}/pulls/{}", base_uri, UPSTREAM_OWNER, UPSTREAM_NAME, number),
+        "id": number,
+        "number": number,
+        "state": "closed",
+        "title": format!("Change #{}", number),
+        "body": format!("This is change #{}.", number),
+        "user": author(user),
+        "labels": [],
+        "html_url": html_url,
+        "diff_url": format!("{}.diff", html_url),
+        "commits_url": format!("{}/commits", html_url),
+        "comments_url": format!("{}/comments", html_url),
+        "created_at": merged_at.unwrap_or_else(recently) - Duration::days(1),
+        "merged_at": merged_at,
+        "merge_commit_sha": if merged_at.is_some() { Some(sha) } else { None },
+        "head": { "ref": format!("change-{}", number), "sha": sha },
+        "base": { "ref": BRANCH, "sha": sha },
+    });
+}
+
+/// A mirror PR the bot opened from `branch` on its fork, in the given state.
+pub fn mirror_pr(env: &TestEnv, number: u64, upstream_number: u64, branch: &str, state: &str) -> Value {
+    let mut pr = pull_request(&env.server.uri(), number, None, "0000000", BOT_LOGIN);
+    pr["title"] = json!(format!("Mirror {}: Change #{}", upstream_number, upstream_number));
+    pr["state"] = json!(state);
+    pr["head"] = json!({ "label": format!("{}:{}", BOT_LOGIN, branch), "ref": branch, "sha": "0000000" });
+    return pr;
+}
+
+pub fn issue(number: u64, title: &str) -> Value {
+    let url = format!("https://api.github.com/repos/{}/{}/issues/{}", TARGET_OWNER, TARGET_NAME, number);
+    return json!({
+        "id": number,
+        "node_id": "MDU6SXNzdWUx",
+        "url": url,
+        "repository_url": format!("https://api.github.com/repos/{}/{}", TARGET_OWNER, TARGET_NAME),
+        "labels_url": format!("{}/labels", url),
+        "comments_url": format!("{}/comments", url),
+        "events_url": format!("{}/events", url),
+        "html_url": format!("https://github.com/{}/{}/issues/{}", TARGET_OWNER, TARGET_NAME, number),
+        "number": number,
+        "state": "open",
+        "title": title,
+        "user": author(BOT_LOGIN),
+        "labels": [],
+        "assignees": [],
+        "author_association": "NONE",
+        "locked": false,
+        "comments": 0,
+        "created_at": Utc::now(),
+        "updated_at": Utc::now(),
+    });
+}
+
+/// A GitHub error response, like `{"message": "Bad credentials"}`.
+pub fn github_error(status: u16, message: &str) -> ResponseTemplate {
+    return ResponseTemplate::new(status).set_body_json(json!({
+        "message": message,
+        "documentation_url": "https://docs.github.com/rest",
+    }));
+}
@@ -0,0 +1,62 @@
+use super::*;
+use crate::{mirrors::MirrorList, reconcile::reconcile};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keeps_the_old_mirror_when_remirroring_fails() {
+    let env = TestEnv::new().await;
+    env.commit_target("notes.txt", None, "Remove notes");
+    let conflicting = env.merge_upstream_pr(5, "notes.txt", Some("Better notes.\n"), recently());
+
+    let mut old_mirror = mirror_pr(&env, 10, 5, "old-mirror", "open");
+    old_mirror["labels"] = json!([{
+        "id": 1,
+        "node_id": "LA_1",
+        "url": format!("https://api.github.com/repos/{}/{}/labels/remirror", TARGET_OWNER, TARGET_NAME),
+        "name": "remirror",
+        "color": "ffffff",
+        "default": false,
+    }]);
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .and(query_param("state", "all"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([old_mirror])))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls/5", UPSTREAM_OWNER, UPSTREAM_NAME)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(conflicting))
+        .mount(&env.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!("/repos/{}/{}/issues/10/comments", TARGET_OWNER, TARGET_NAME)))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({})))
+        .mount(&env.server)
+        .await;
+
+    reconcile(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    let comments = env.requests_to("POST", &format!("/repos/{}/{}/issues/10/comments", TARGET_OWNER, TARGET_NAME)).await;
+    assert_eq!(comments.len(), 1);
+    assert!(comments[0]["body"].as_str().unwrap().contains("Failed to re-mirror"), "{}", comments[0]);
+    assert!(env.requests_to("PATCH", &format!("/repos/{}/{}/pulls/10", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+    assert!(env.requests_to("DELETE", &format!("/repos/{}/{}/issues/10/labels/remirror", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+    assert!(env.requests_to("POST", &format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn only_counts_prs_the_bot_made_as_mirrors() {
+    let env = TestEnv::new().await;
+
+    let mut lookalike = mirror_pr(&env, 11, 6, "lookalike", "open");
+    lookalike["user"] = author("someone-else");
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}/pulls", TARGET_OWNER, TARGET_NAME)))
+        .and(query_param("state", "all"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([mirror_pr(&env, 10, 5, "mirror", "open"), lookalike])))
+        .mount(&env.server)
+        .await;
+
+    let mirrors = crate::mirrors::get_mirror_prs(&env.octocrab(), &env.config, &env.bot_info()).await.unwrap();
+
+    assert_eq!(mirrors.iter().map(|m| m.upstream_number).collect::<Vec<_>>(), vec![5]);
+}
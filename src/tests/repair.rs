@@ -0,0 +1,48 @@
+use super::*;
+use crate::{git_utils, mirror_prs, mirrors::MirrorList};
+use git2::RepositoryState;
+use std::fs;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repairs_a_repo_left_mid_cherry_pick() {
+    let env = TestEnv::new().await;
+    let repo = git_utils::ensure_repo(&env.config, &env.bot_info()).unwrap();
+    let workdir = repo.workdir().unwrap().to_path_buf();
+    let head = repo.head().unwrap().peel_to_commit().unwrap().id();
+
+    // What a crash part way through cherry-picking could leave behind.
+    fs::write(repo.path().join("CHERRY_PICK_HEAD"), format!("{}\n", head)).unwrap();
+    fs::write(workdir.join("README.md"), "<<<<<<< ours\n").unwrap();
+    fs::write(workdir.join("leftover.txt"), "Not committed.\n").unwrap();
+    repo.set_head_detached(head).unwrap();
+    repo.remote_set_url("cloned", "file:///nowhere").unwrap();
+    repo.remote_delete("upstream").unwrap();
+
+    let repairs = git_utils::repair_repo(&env.config).unwrap();
+
+    assert_eq!(repairs.len(), 6, "{:?}", repairs);
+    assert_eq!(repo.state(), RepositoryState::Clean);
+    assert!(!repo.head_detached().unwrap());
+    assert_eq!(fs::read_to_string(workdir.join("README.md")).unwrap(), "A game.\n");
+    assert!(!workdir.join("leftover.txt").exists());
+    assert_eq!(repo.find_remote("cloned").unwrap().url(), Some(env.git_url(UPSTREAM_OWNER, UPSTREAM_NAME).as_str()));
+    assert_eq!(repo.find_remote("upstream").unwrap().url(), Some(env.git_url(TARGET_OWNER, TARGET_NAME).as_str()));
+
+    assert!(git_utils::repair_repo(&env.config).unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clones_again_when_the_repo_is_beyond_repair() {
+    let env = TestEnv::new().await;
+    let repo = git_utils::ensure_repo(&env.config, &env.bot_info()).unwrap();
+    fs::write(repo.path().join("HEAD"), "garbage").unwrap();
+    drop(repo);
+    let mirrored = env.merge_upstream_pr(2, "feature.txt", Some("A new feature.\n"), recently());
+    env.mount_pr_pages(vec![vec![mirrored]]).await;
+    env.mount_pr_creation(100).await;
+
+    let report = mirror_prs(&env.octocrab(), &env.config, &env.bot_info(), &MirrorList::default()).await;
+
+    assert!(report.succeeded());
+    assert!(Repository::open(env.config.get_repo_path()).unwrap().head().is_ok());
+}
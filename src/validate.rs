@@ -0,0 +1,542 @@
+use crate::{forge::{self, ForgeKind}, AppConfig, Error, NotifierKind, FILE_NAME};
+use octocrab::params::repos::Reference;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_path_to_error::Segment;
+use serde_yaml::Value;
+use std::fs;
+
+/// Stops looking for more problems after this many, in case fixing one up keeps causing another.
+const MAX_PROBLEMS: usize = 50;
+
+/// A problem with the config file, and where in it if we could tell.
+pub struct Problem {
+    location: Option<(usize, usize)>,
+    path: String,
+    message: String,
+}
+
+impl core::fmt::Display for Problem {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        return write!(f, "{}", self.message);
+    }
+}
+
+/// The result of checking something against GitHub.
+struct Check {
+    passed: bool,
+    /// Worth knowing, but won't stop the bot from working.
+    warning: bool,
+    message: String,
+}
+
+impl Check {
+    fn pass(message: String) -> Self {
+        return Check { passed: true, warning: false, message };
+    }
+
+    fn fail(message: String) -> Self {
+        return Check { passed: false, warning: false, message };
+    }
+
+    fn warn(message: String) -> Self {
+        return Check { passed: false, warning: true, message };
+    }
+}
+
+/// Runs the `validate` command, printing every problem found and returning the exit code.
+pub async fn run() -> i32 {
+    println!("Validating {}.", FILE_NAME);
+
+    let text = match fs::read_to_string(FILE_NAME) {
+        Ok(t) => t,
+        Err(e) => {
+            let e = Error::Config(format!("Couldn't read {}: {}", FILE_NAME, e));
+            println!("error: {}", e);
+            return e.exit_code();
+        }
+    };
+
+    let problems = parse_problems(&text);
+    let warnings = match serde_yaml::from_str::<Value>(&text) {
+        Ok(value) => unknown_keys(&text, &value),
+        Err(_) => Vec::new(),
+    };
+
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    for problem in &problems {
+        println!("error: {}", problem);
+    }
+
+    if !problems.is_empty() {
+        println!("Found {} problems in {}.", problems.len(), FILE_NAME);
+        return Error::Config(String::new()).exit_code();
+    }
+
+    // Parsing worked above, so this only fails if the file changed since.
+    let config: AppConfig = match serde_yaml::from_str(&text) {
+        Ok(c) => c,
+        Err(e) => {
+            let e = Error::Config(e.to_string());
+            println!("error: {}", e);
+            return e.exit_code();
+        }
+    };
+
+    let checks = check_remote(&config).await;
+    let mut failed = false;
+    for check in &checks {
+        let status = match (check.passed, check.warning) {
+            (true, _) => "ok",
+            (false, true) => "warning",
+            (false, false) => "error",
+        };
+        println!("{}: {}", status, check.message);
+        failed |= !check.passed && !check.warning;
+    }
+
+    if failed {
+        println!("Some checks against GitHub failed.");
+        return Error::from("remote checks failed").exit_code();
+    }
+
+    println!("{} looks good.", FILE_NAME);
+    return 0;
+}
+
+/// A JSON Schema for the config file, for editors to validate it against.
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(AppConfig);
+    return serde_json::to_string_pretty(&schema).unwrap_or_default();
+}
+
+/// Finds every problem stopping the config file from being read, instead of only the first.
+///
+/// After each problem the offending value is dropped, or filled in from the defaults if it's missing, and parsing is tried again.
+pub fn parse_problems(text: &str) -> Vec<Problem> {
+    let mut value: Value = match serde_yaml::from_str(text) {
+        Ok(v) => v,
+        Err(e) => {
+            return vec![Problem {
+                location: e.location().map(|l| (l.line(), l.column())),
+                path: String::new(),
+                message: e.to_string(),
+            }];
+        }
+    };
+
+    let defaults = serde_yaml::to_value(AppConfig::default()).unwrap_or_default();
+    let mut problems = Vec::new();
+
+    while problems.len() < MAX_PROBLEMS {
+        let error = match serde_path_to_error::deserialize::<_, AppConfig>(value.clone()) {
+            Ok(_) => break,
+            Err(e) => e,
+        };
+
+        let mut path: Vec<Segment> = error.path().iter().cloned().collect();
+        let message = error.inner().to_string();
+
+        // A missing field is reported on the struct that's missing it.
+        let missing = message
+            .strip_prefix("missing field `")
+            .and_then(|m| m.split('`').next())
+            .map(|m| m.to_string());
+
+        if let Some(field) = &missing {
+            path.push(Segment::Map { key: field.clone() });
+        }
+
+        // A field whose bad value was dropped shows up again as missing, it's already been reported.
+        let reported = problems.iter().any(|p: &Problem| p.path == path_string(&path));
+        if !reported {
+            problems.push(Problem {
+                location: locate(text, &path),
+                path: path_string(&path),
+                message: message.clone(),
+            });
+        }
+
+        let fixed = match missing {
+            Some(_) => {
+                match lookup(&defaults, &path) {
+                    Some(default) => replace(&mut value, &path, Some(default.clone())),
+                    None => false,
+                }
+            }
+            None => replace(&mut value, &path, None),
+        };
+
+        if !fixed {
+            break;
+        }
+    }
+
+    return problems;
+}
+
+/// Finds keys the bot doesn't know about, which are usually typos.
+fn unknown_keys(text: &str, value: &Value) -> Vec<Problem> {
+    let schema = schemars::schema_for!(AppConfig);
+    let mut warnings = Vec::new();
+    check_keys(&schema, &schema.schema, value, &mut Vec::new(), text, &mut warnings);
+    return warnings;
+}
+
+fn check_keys(root: &RootSchema, schema: &SchemaObject, value: &Value, path: &mut Vec<Segment>, text: &str, warnings: &mut Vec<Problem>) {
+    let schema = resolve(root, schema);
+
+    match value {
+        Value::Mapping(mapping) => {
+            let object = match &schema.object {
+                Some(o) => o,
+                None => return,
+            };
+
+            for (key, child) in mapping {
+                let key = match key.as_str() {
+                    Some(k) => k.to_string(),
+                    None => continue,
+                };
+
+                path.push(Segment::Map { key: key.clone() });
+                match object.properties.get(&key) {
+                    Some(Schema::Object(child_schema)) => check_keys(root, child_schema, child, path, text, warnings),
+                    Some(Schema::Bool(_)) => {}
+                    None => warnings.push(Problem {
+                        location: locate(text, path),
+                        path: path_string(path),
+                        message: "unknown key, it will be ignored".to_string(),
+                    }),
+                }
+                path.pop();
+            }
+        }
+        Value::Sequence(items) => {
+            let item_schema = match schema.array.as_ref().and_then(|a| a.items.as_ref()) {
+                Some(SingleOrVec::Single(s)) => match s.as_ref() {
+                    Schema::Object(o) => o,
+                    Schema::Bool(_) => return,
+                },
+                _ => return,
+            };
+
+            for (index, item) in items.iter().enumerate() {
+                path.push(Segment::Seq { index });
+                check_keys(root, item_schema, item, path, text, warnings);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Follows `$ref`s, and `Option`s which schemars makes into an `anyOf` with null, to the schema that actually describes a value.
+fn resolve<'a>(root: &'a RootSchema, schema: &'a SchemaObject) -> &'a SchemaObject {
+    if let Some(name) = schema.reference.as_ref().and_then(|r| r.strip_prefix("#/definitions/")) {
+        if let Some(Schema::Object(definition)) = root.definitions.get(name) {
+            return resolve(root, definition);
+        }
+    }
+
+    let any_of = schema.subschemas.as_ref().and_then(|s| s.any_of.as_ref().or(s.all_of.as_ref()));
+    if let Some(options) = any_of {
+        let is_null = |s: &SchemaObject| s.instance_type == Some(SingleOrVec::Single(Box::new(InstanceType::Null)));
+        let not_null = options.iter().find_map(|s| match s {
+            Schema::Object(o) if !is_null(o) => Some(o),
+            _ => None,
+        });
+
+        if let Some(o) = not_null {
+            return resolve(root, o);
+        }
+    }
+
+    return schema;
+}
+
+/// Checks that the tokens work and the repos and branches exist, and that the bot can push its mirrors.
+async fn check_remote(config: &AppConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let org = match forge::connect(&config.into_repo, &config.org_token) {
+        Ok(o) => o,
+        Err(e) => return vec![Check::fail(format!("Couldn't set up a client for into_repo: {}", e))],
+    };
+    let bot = match forge::connect(&config.into_repo, &config.bot_token) {
+        Ok(o) => o,
+        Err(e) => return vec![Check::fail(format!("Couldn't set up a client for into_repo: {}", e))],
+    };
+
+    match org.current_user().await {
+        Ok(u) => checks.push(Check::pass(format!("org_token authenticates as {}", u.login))),
+        Err(e) => checks.push(Check::fail(format!("org_token doesn't authenticate: {}", e))),
+    }
+
+    let bot_login = match bot.current_user().await {
+        Ok(u) => {
+            checks.push(Check::pass(format!("bot_token authenticates as {}", u.login)));
+            Some(u.login)
+        }
+        Err(e) => {
+            checks.push(Check::fail(format!("bot_token doesn't authenticate: {}", e)));
+            None
+        }
+    };
+
+    let clone_token = config.clone_token.as_ref().unwrap_or(&config.org_token);
+    for (setting, repo_info, token) in [("clone_repo", &config.clone_repo, clone_token), ("into_repo", &config.into_repo, &config.org_token)] {
+        let name = format!("{}/{}", repo_info.owner, repo_info.name);
+
+        if repo_info.forge == ForgeKind::Git {
+            match forge::git::has_branch(repo_info) {
+                Ok(true) => checks.push(Check::pass(format!("{} branch {} exists", setting, repo_info.branch))),
+                Ok(false) => checks.push(Check::fail(format!("{} branch {} couldn't be found", setting, repo_info.branch))),
+                Err(e) => checks.push(Check::fail(format!("{} {} couldn't be read: {}", setting, name, e))),
+            }
+            continue;
+        }
+
+        // Only GitHub says enough about a repo to check more than that it's there.
+        if repo_info.forge != ForgeKind::Github {
+            let client = match forge::connect(repo_info, token) {
+                Ok(c) => c,
+                Err(e) => {
+                    checks.push(Check::fail(format!("Couldn't set up a client for {}: {}", setting, e)));
+                    continue;
+                }
+            };
+            match client.list_closed_prs(repo_info, 1).await {
+                Ok(_) => checks.push(Check::pass(format!("{} {} exists", setting, name))),
+                Err(e) => checks.push(Check::fail(format!("{} {} couldn't be found: {}", setting, name, e))),
+            }
+            continue;
+        }
+
+        let client = match forge::github_client(repo_info, token) {
+            Ok(c) => c,
+            Err(e) => {
+                checks.push(Check::fail(format!("Couldn't set up a GitHub client for {}: {}", setting, Error::from(e))));
+                continue;
+            }
+        };
+        let repo = match client.repos(&repo_info.owner, &repo_info.name).get().await {
+            Ok(r) => {
+                checks.push(Check::pass(format!("{} {} exists", setting, name)));
+                r
+            }
+            Err(e) => {
+                checks.push(Check::fail(format!("{} {} couldn't be found: {}", setting, name, Error::from(e))));
+                continue;
+            }
+        };
+
+        match client.repos(&repo_info.owner, &repo_info.name).get_ref(&Reference::Branch(repo_info.branch.clone())).await {
+            Ok(_) => checks.push(Check::pass(format!("{} branch {} exists", setting, repo_info.branch))),
+            Err(e) => checks.push(Check::fail(format!("{} branch {} couldn't be found: {}", setting, repo_info.branch, Error::from(e)))),
+        }
+
+        if setting != "into_repo" {
+            continue;
+        }
+
+        match repo.permissions.as_ref().map(|p| p.push) {
+            Some(true) => checks.push(Check::pass(format!("org_token has write access to {}, for labels, assignees and reviewers", name))),
+            _ => checks.push(Check::warn(format!("org_token doesn't have write access to {}, so labels, assignees and reviewers can't be set", name))),
+        }
+
+        let bot_login = match &bot_login {
+            Some(l) => l,
+            None => continue,
+        };
+
+        let bot = match forge::github_client(repo_info, &config.bot_token) {
+            Ok(b) => b,
+            Err(_) => continue,
+        };
+        if !config.pushes_to_fork() {
+            match bot.repos(&repo_info.owner, &repo_info.name).get().await {
+                Ok(r) if r.permissions.as_ref().is_some_and(|p| p.push) => checks.push(Check::pass(format!("{} can push mirror branches to {}", bot_login, name))),
+                _ => checks.push(Check::fail(format!("{} can't push to {}, which push_mode: same_repo needs", bot_login, name))),
+            }
+            continue;
+        }
+
+        match bot.repos(bot_login, &repo_info.name).get().await {
+            Ok(fork) if fork.permissions.as_ref().is_some_and(|p| p.push) => checks.push(Check::pass(format!("{} can push to its fork {}/{}", bot_login, bot_login, repo_info.name))),
+            Ok(_) => checks.push(Check::fail(format!("{} can't push to {}/{}, check the scopes of bot_token", bot_login, bot_login, repo_info.name))),
+            Err(_) if repo.allow_forking == Some(false) => checks.push(Check::fail(format!("{} has no fork of {} yet, and the repository doesn't allow forking", bot_login, name))),
+            Err(_) => checks.push(Check::pass(format!("{} has no fork of {} yet, it will be made on the first run", bot_login, name))),
+        }
+    }
+
+    let github_only = config.github_only_settings();
+    if !github_only.is_empty() {
+        checks.push(Check::warn(format!("into_repo isn't on GitHub, so these settings are ignored: {}", github_only.join(", "))));
+    }
+
+    if config.branch_prefix.as_deref().is_some_and(|p| p.trim().is_empty()) {
+        checks.push(Check::fail("branch_prefix can't be empty, cleanup would take every branch of into_repo for a mirror branch".to_string()));
+    }
+
+    for rule in &config.label_map.rules {
+        if let Err(e) = rule.regex() {
+            checks.push(Check::fail(format!("label rule for '{}' is invalid: {}", rule.to, e)));
+        }
+    }
+
+    for notifier in &config.notifications {
+        if notifier.kind == NotifierKind::Matrix && (notifier.room.is_none() || notifier.token.is_none()) {
+            checks.push(Check::fail(format!("Matrix notifier for {} needs both 'room' and 'token'", notifier.url)));
+        }
+    }
+
+    return checks;
+}
+
+fn path_string(path: &[Segment]) -> String {
+    let mut string = String::new();
+    for segment in path {
+        match segment {
+            Segment::Seq { index } => string.push_str(&format!("[{}]", index)),
+            Segment::Map { key } if string.is_empty() => string.push_str(key),
+            Segment::Map { key } => string.push_str(&format!(".{}", key)),
+            Segment::Enum { variant } => string.push_str(&format!("::{}", variant)),
+            Segment::Unknown => string.push_str(".?"),
+        }
+    }
+
+    return string;
+}
+
+fn lookup<'a>(value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    let mut current = value;
+    for segment in path {
+        current = match segment {
+            Segment::Seq { index } => current.as_sequence()?.get(*index)?,
+            Segment::Map { key } => current.as_mapping()?.get(key.as_str())?,
+            _ => return None,
+        };
+    }
+
+    return Some(current);
+}
+
+/// Replaces the value at `path`, or removes it if `with` is `None`. Returns whether anything changed.
+fn replace(value: &mut Value, path: &[Segment], with: Option<Value>) -> bool {
+    let (last, parents) = match path.split_last() {
+        Some(p) => p,
+        None => return false,
+    };
+
+    let mut current = value;
+    for segment in parents {
+        current = match segment {
+            Segment::Seq { index } => match current.as_sequence_mut().and_then(|s| s.get_mut(*index)) {
+                Some(v) => v,
+                None => return false,
+            },
+            Segment::Map { key } => match current.as_mapping_mut().and_then(|m| m.get_mut(key.as_str())) {
+                Some(v) => v,
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+
+    return match (last, current, with) {
+        (Segment::Seq { index }, Value::Sequence(items), None) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        (Segment::Seq { index }, Value::Sequence(items), Some(v)) if *index < items.len() => {
+            items[*index] = v;
+            true
+        }
+        (Segment::Map { key }, Value::Mapping(mapping), None) => mapping.remove(key.as_str()).is_some(),
+        (Segment::Map { key }, Value::Mapping(mapping), Some(v)) => {
+            mapping.insert(Value::String(key.clone()), v);
+            true
+        }
+        _ => false,
+    };
+}
+
+/// Finds the line and column of a path in block style YAML, as closely as it can.
+fn locate(text: &str, path: &[Segment]) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    // Where to start looking, and how far in the parent is. Children are further in, except sequence items which can line up with their key.
+    let mut from = 0;
+    let mut parent_indent: isize = -1;
+    // Whether `from` is the parent's own line, as with sequence items.
+    let mut on_parent_line = false;
+    let mut location = None;
+
+    for segment in path {
+        let mut found = None;
+        let mut seen = 0;
+        let mut item_indent = None;
+
+        for (i, line) in lines.iter().enumerate().skip(from) {
+            let content = line.trim_start();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            let indent = (line.len() - content.len()) as isize;
+            let is_item = content.starts_with("- ") || content == "-";
+            let leaving_parent = indent <= parent_indent && !(on_parent_line && i == from);
+
+            match segment {
+                Segment::Map { key } => {
+                    if leaving_parent {
+                        break;
+                    }
+                    // Keys can start on the same line as the sequence item holding them.
+                    let (content, indent) = match content.strip_prefix("- ") {
+                        Some(rest) => (rest.trim_start(), indent + (content.len() - rest.trim_start().len()) as isize),
+                        None => (content, indent),
+                    };
+                    if content.starts_with(&format!("{}:", key)) && indent > parent_indent {
+                        found = Some((i, indent));
+                        break;
+                    }
+                }
+                Segment::Seq { index } => {
+                    if !is_item && leaving_parent {
+                        break;
+                    }
+                    if !is_item || item_indent.is_some_and(|n| n != indent) {
+                        continue;
+                    }
+                    item_indent = Some(indent);
+                    if seen == *index {
+                        found = Some((i, indent));
+                        break;
+                    }
+                    seen += 1;
+                }
+                _ => return location,
+            }
+        }
+
+        let (line, indent) = match found {
+            Some(f) => f,
+            None => return location,
+        };
+
+        location = Some((line + 1, indent as usize + 1));
+        on_parent_line = matches!(segment, Segment::Seq { .. });
+        from = if on_parent_line { line } else { line + 1 };
+        parent_indent = indent;
+    }
+
+    return location;
+}